@@ -2,7 +2,11 @@
 
 use std::time::Duration;
 
-use common::{CreateStats, ErrorReport, HookStats, StatReport};
+use chrono::Utc;
+use common::{
+    ErrorReport, GetPrunePolicyResponse, HostInventory, PrunePolicy, PruneStats, RestoreReport,
+    StatReport,
+};
 use log::info;
 use reqwest::header::{HeaderMap, HeaderValue};
 use reqwest::Response;
@@ -20,11 +24,14 @@ struct ErrorMessage {
 pub struct Api {
     address: Url,
     client: reqwest::Client,
+    host: HostInventory,
 }
 
 impl Api {
     /// Create a new api instance
-    pub fn new(address: Url, token: &str) -> Result<Self, String> {
+    ///
+    /// The inventory of the host is sent with every report.
+    pub fn new(address: Url, token: &str, host: HostInventory) -> Result<Self, String> {
         let mut header = HeaderMap::new();
         header.insert(
             "Authorization",
@@ -39,7 +46,11 @@ impl Api {
             .build()
             .map_err(|e| format!("Could not create API client: {e}"))?;
 
-        Ok(Self { address, client })
+        Ok(Self {
+            address,
+            client,
+            host,
+        })
     }
 
     async fn check_error(&self, res: Response) -> Result<(), String> {
@@ -70,6 +81,10 @@ impl Api {
 
     /// Send an error to the vinculum
     pub async fn send_error(&self, error_report: ErrorReport) -> Result<(), String> {
+        let error_report = ErrorReport {
+            host: Some(self.host.clone()),
+            ..error_report
+        };
         let res = self
             .client
             .post(self.address.join("/api/drone/v1/error").unwrap())
@@ -84,20 +99,16 @@ impl Api {
     }
 
     /// Send stats to the vinculum
-    pub async fn send_stats(
-        &self,
-        pre_hook_stats: Option<HookStats>,
-        create_stats: CreateStats,
-        post_hook_stats: Option<HookStats>,
-    ) -> Result<(), String> {
-        let stat_report = StatReport {
-            pre_hook_stats,
-            create_stats,
-            post_hook_stats,
-        };
-
+    ///
+    /// `sent_at` of the report is set to the current time.
+    pub async fn send_stats(&self, stat_report: &StatReport) -> Result<(), String> {
         info!("Stats: {stat_report:#?}");
 
+        let stat_report = StatReport {
+            sent_at: Some(Utc::now()),
+            host: Some(self.host.clone()),
+            ..stat_report.clone()
+        };
         let res = self
             .client
             .post(self.address.join("/api/drone/v1/stats").unwrap())
@@ -110,4 +121,66 @@ impl Api {
 
         Ok(())
     }
+
+    /// Send the report of a restore to the vinculum
+    pub async fn send_restore(&self, restore_report: &RestoreReport) -> Result<(), String> {
+        let restore_report = RestoreReport {
+            host: Some(self.host.clone()),
+            ..restore_report.clone()
+        };
+        let res = self
+            .client
+            .post(self.address.join("/api/drone/v1/restore").unwrap())
+            .json(&restore_report)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        self.check_error(res).await?;
+
+        Ok(())
+    }
+
+    /// Retrieve the retention policy that is configured in the vinculum
+    pub async fn get_prune_policy(&self) -> Result<Option<PrunePolicy>, String> {
+        let res = self
+            .client
+            .get(self.address.join("/api/drone/v1/prune-policy").unwrap())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if res.status() != 200 {
+            self.check_error(res).await?;
+            return Err("Unexpected response from vinculum".to_string());
+        }
+
+        let policy: GetPrunePolicyResponse = res
+            .json()
+            .await
+            .map_err(|e| format!("Could not deserialize prune policy: {e}"))?;
+
+        Ok(policy.policy)
+    }
+
+    /// Send the stats of a prune to the vinculum
+    pub async fn send_prune(&self, prune_stats: PruneStats) -> Result<(), String> {
+        info!("Prune stats: {prune_stats:#?}");
+
+        let prune_stats = PruneStats {
+            host: Some(self.host.clone()),
+            ..prune_stats
+        };
+        let res = self
+            .client
+            .post(self.address.join("/api/drone/v1/prune").unwrap())
+            .json(&prune_stats)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        self.check_error(res).await?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,444 @@
+//! Execution of the borg commands that are not covered by [borgbackup::asynchronous],
+//! or whose output is needed in more detail
+//!
+//! The commands that are needed by the vinculum as well are found in [common::borg].
+
+use std::path::Path;
+use std::process::{ExitStatus, Output, Stdio};
+
+use borgbackup::asynchronous::CreateProgress;
+use borgbackup::common::CommonOptions;
+use borgbackup::output::create::Create;
+use borgbackup::output::logging::{LevelName, LoggingMessage};
+use common::borg;
+use common::{BorgWarning, ChangedPath, FileChange, PrunePolicy};
+use log::{trace, warn};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::sync::mpsc;
+
+use crate::config::Config;
+
+/// Build the [CommonOptions] using the settings from [Config].
+pub fn common_options(config: &Config) -> CommonOptions {
+    CommonOptions {
+        remote_path: config.borg.remote_path.clone(),
+        rsh: Some("ssh -o 'StrictHostKeyChecking accept-new'".to_string()),
+        ..CommonOptions::default()
+    }
+}
+
+/// Build the command to execute borg with the given arguments using the settings from [Config].
+///
+/// `args` are the arguments after the common options, e.g. `info --json <repository>`.
+fn borg_command(config: &Config, args: &str) -> Result<tokio::process::Command, String> {
+    borg::borg_command(&common_options(config), &config.borg.passphrase, args)
+        .map_err(|e| e.to_string())
+}
+
+/// Execute borg with the given arguments and collect its output
+async fn execute_borg(config: &Config, args: &str) -> Result<Output, String> {
+    borg::execute_borg(&common_options(config), &config.borg.passphrase, args)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Check the exit status of borg and collect the error messages it logged to `stderr`
+fn check_status(status: ExitStatus, stderr: &[u8]) -> Result<(), String> {
+    borg::check_status(status, stderr).map_err(|e| e.to_string())
+}
+
+/// The quoted location of the repository or an archive in it
+fn location(config: &Config, archive: Option<&str>) -> String {
+    match archive {
+        None => shlex::quote(&config.borg.repository).to_string(),
+        Some(archive) => borg::archive_spec(&config.borg.repository, archive),
+    }
+}
+
+/// Build the path prefix patterns for paths in an archive
+fn path_patterns(paths: &[String]) -> String {
+    paths
+        .iter()
+        .map(|x| borg::path_pattern(x))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Retrieve the version of borg, e.g. `1.2.4`
+pub async fn version(config: &Config) -> Result<String, String> {
+    let res = execute_borg(config, "--version").await?;
+    check_status(res.status, &res.stderr)?;
+
+    let output = String::from_utf8_lossy(&res.stdout);
+    Ok(output.trim().trim_start_matches("borg").trim().to_string())
+}
+
+/// The progress of the extraction of an archive
+#[derive(Clone, Debug)]
+pub struct ExtractProgress {
+    /// The number of bytes that were extracted
+    pub current: u64,
+    /// The total number of bytes that are extracted
+    pub total: u64,
+    /// The path of the current item
+    pub path: String,
+}
+
+/// Extract an archive into `target`.
+///
+/// If `paths` is not empty, only the given paths are extracted.
+/// If `progress_channel` is set, the progress is sent to it.
+pub async fn extract(
+    config: &Config,
+    archive: &str,
+    paths: &[String],
+    target: &Path,
+    progress_channel: Option<mpsc::Sender<ExtractProgress>>,
+) -> Result<(), String> {
+    let progress = if progress_channel.is_some() {
+        " --progress"
+    } else {
+        ""
+    };
+
+    let mut child = borg_command(
+        config,
+        &format!(
+            "extract{progress} -- {} {}",
+            location(config, Some(archive)),
+            path_patterns(paths)
+        ),
+    )?
+    .current_dir(target)
+    .stdout(Stdio::null())
+    .stderr(Stdio::piped())
+    .kill_on_drop(true)
+    .spawn()
+    .map_err(|e| format!("Could not execute borg: {e}"))?;
+
+    let stderr = child
+        .stderr
+        .take()
+        .ok_or("Could not capture the output of borg")?;
+    let mut lines = BufReader::new(stderr).lines();
+
+    let mut output = vec![];
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| format!("Could not read the output of borg: {e}"))?
+    {
+        if let Ok(LoggingMessage::ProgressPercent {
+            finished: false,
+            current: Some(current),
+            total: Some(total),
+            info,
+            ..
+        }) = serde_json::from_str(&line)
+        {
+            if let Some(tx) = &progress_channel {
+                let path = info
+                    .and_then(|x| x.first().and_then(|x| x.as_str().map(str::to_string)))
+                    .unwrap_or_default();
+                if tx
+                    .send(ExtractProgress {
+                        current,
+                        total,
+                        path,
+                    })
+                    .await
+                    .is_err()
+                {
+                    trace!("Progress channel was closed");
+                }
+            }
+            continue;
+        }
+
+        output.extend_from_slice(line.as_bytes());
+        output.push(b'\n');
+    }
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| format!("Could not wait for borg: {e}"))?;
+
+    check_status(status, &output)
+}
+
+/// The maximum accumulated length of the changed paths that are collected by [create]
+const MAX_CHANGED_PATHS_LENGTH: usize = 64 * 1024;
+
+/// The outcome of `borg create`
+#[derive(Clone, Debug)]
+pub struct CreateOutput {
+    /// The stats of the created archive
+    pub stats: Create,
+    /// The warnings borg logged
+    pub warnings: Vec<BorgWarning>,
+    /// The number of added files
+    pub added: u64,
+    /// The number of modified files
+    pub modified: u64,
+    /// The added and modified paths, capped at [MAX_CHANGED_PATHS_LENGTH] bytes
+    pub changed_paths: Vec<ChangedPath>,
+    /// Whether not all changed paths are included in `changed_paths`
+    pub changed_paths_truncated: bool,
+}
+
+/// Create an archive using the settings from [Config].
+///
+/// Other than [borgbackup::asynchronous::create], this collects the warnings borg logged
+/// and the files that were added or modified.
+/// If `progress_channel` is set, the progress is sent to it.
+pub async fn create(
+    config: &Config,
+    progress_channel: Option<mpsc::Sender<CreateProgress>>,
+) -> Result<CreateOutput, String> {
+    let progress = if progress_channel.is_some() {
+        " --progress"
+    } else {
+        ""
+    };
+
+    // Added (A) and modified (M) files are listed to collect the changes.
+    // Files that changed while being backed up (C) or could not be read (E) are listed,
+    // to assign the logged warnings to their paths.
+    // Unchanged files are not listed, as they are derived from the number of files.
+    let mut child = borg_command(
+        config,
+        &format!(
+            "create --json{progress} --list --filter=AMCE --compression {compression} --sparse \
+            --patterns-from {patterns} -- {location}",
+            compression = shlex::quote(&config.borg.compression),
+            patterns = shlex::quote(&config.borg.pattern_file_path),
+            location = location(config, Some("{utcnow}")),
+        ),
+    )?
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .kill_on_drop(true)
+    .spawn()
+    .map_err(|e| format!("Could not execute borg: {e}"))?;
+
+    let mut stdout = child
+        .stdout
+        .take()
+        .ok_or("Could not capture the output of borg")?;
+    let stderr = child
+        .stderr
+        .take()
+        .ok_or("Could not capture the output of borg")?;
+    let mut lines = BufReader::new(stderr).lines();
+
+    let mut output = vec![];
+    let mut paths = vec![];
+    let mut messages = vec![];
+    let mut added = 0;
+    let mut modified = 0;
+    let mut changed_paths = vec![];
+    let mut changed_paths_length = 0;
+    let mut changed_paths_truncated = false;
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| format!("Could not read the output of borg: {e}"))?
+    {
+        match serde_json::from_str(&line) {
+            Ok(LoggingMessage::ArchiveProgress {
+                original_size: Some(original_size),
+                compressed_size: Some(compressed_size),
+                deduplicated_size: Some(deduplicated_size),
+                nfiles: Some(nfiles),
+                path: Some(path),
+                finished: false,
+                ..
+            }) => {
+                if let Some(tx) = &progress_channel {
+                    let progress = CreateProgress::Progress {
+                        original_size,
+                        compressed_size,
+                        deduplicated_size,
+                        nfiles,
+                        path,
+                    };
+                    if tx.send(progress).await.is_err() {
+                        trace!("Progress channel was closed");
+                    }
+                }
+                continue;
+            }
+            Ok(LoggingMessage::FileStatus { status, path }) => {
+                let change = match status.as_str() {
+                    "A" => FileChange::Added,
+                    "M" => FileChange::Modified,
+                    _ => {
+                        paths.push(path);
+                        continue;
+                    }
+                };
+
+                match change {
+                    FileChange::Added => added += 1,
+                    FileChange::Modified => modified += 1,
+                }
+
+                changed_paths_length += path.len();
+                if changed_paths_length > MAX_CHANGED_PATHS_LENGTH {
+                    changed_paths_truncated = true;
+                } else {
+                    changed_paths.push(ChangedPath { change, path });
+                }
+                continue;
+            }
+            Ok(LoggingMessage::LogMessage {
+                level_name: LevelName::Warning,
+                message,
+                ..
+            }) => {
+                warn!("borg: {message}");
+                messages.push(message);
+            }
+            _ => {}
+        }
+
+        output.extend_from_slice(line.as_bytes());
+        output.push(b'\n');
+    }
+
+    let mut json = vec![];
+    stdout
+        .read_to_end(&mut json)
+        .await
+        .map_err(|e| format!("Could not read the output of borg: {e}"))?;
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| format!("Could not wait for borg: {e}"))?;
+    check_status(status, &output)?;
+
+    let stats =
+        serde_json::from_slice(&json).map_err(|e| format!("Could not parse borg output: {e}"))?;
+
+    // The messages about files start with their path
+    let warnings = messages
+        .into_iter()
+        .map(|message| BorgWarning {
+            path: paths
+                .iter()
+                .find(|path| message.starts_with(&format!("{path}: ")))
+                .cloned(),
+            message,
+        })
+        .collect();
+
+    Ok(CreateOutput {
+        stats,
+        warnings,
+        added,
+        modified,
+        changed_paths,
+        changed_paths_truncated,
+    })
+}
+
+/// The outcome of `borg create --dry-run`
+#[derive(Clone, Debug)]
+pub struct CreateDryRunOutput {
+    /// The paths that would be included in the archive
+    pub paths: Vec<String>,
+    /// The errors and warnings borg logged, e.g. unreadable files
+    pub problems: Vec<String>,
+    /// Whether borg exited without warnings or errors
+    pub success: bool,
+}
+
+/// Simulate the creation of an archive using the settings from [Config].
+///
+/// The paths that would be included are collected from `--list`.
+/// [borgbackup::asynchronous::create] does not support `--dry-run`, so borg is executed directly.
+pub async fn create_dry_run(config: &Config) -> Result<CreateDryRunOutput, String> {
+    let res = execute_borg(
+        config,
+        &format!(
+            "create --dry-run --list --sparse --patterns-from {patterns} -- {location}",
+            patterns = shlex::quote(&config.borg.pattern_file_path),
+            location = location(config, Some("{utcnow}")),
+        ),
+    )
+    .await?;
+    let exit_code = res.status.code().ok_or("Borg was terminated by a signal")?;
+
+    let paths = String::from_utf8_lossy(&res.stderr)
+        .lines()
+        .filter_map(|line| match serde_json::from_str(line) {
+            // Excluded paths are listed with status x
+            Ok(LoggingMessage::FileStatus { status, path }) if status != "x" => Some(path),
+            _ => None,
+        })
+        .collect();
+
+    Ok(CreateDryRunOutput {
+        paths,
+        problems: borg::logged_problems(&res.stderr, true),
+        success: exit_code == 0,
+    })
+}
+
+/// The outcome of `borg prune`
+#[derive(Copy, Clone, Debug)]
+pub struct PruneOutput {
+    /// The number of archives that were pruned, or would be pruned on a dry run
+    pub pruned: u64,
+    /// The number of archives that were kept
+    pub kept: u64,
+}
+
+/// Prune the archives of the repository according to `policy`.
+///
+/// On a dry run, the archives are only listed, but not pruned.
+pub async fn prune(
+    config: &Config,
+    policy: &PrunePolicy,
+    dry_run: bool,
+) -> Result<PruneOutput, String> {
+    let mut args = "prune --list".to_string();
+    if dry_run {
+        args.push_str(" --dry-run");
+    }
+    if let Some(keep_within) = &policy.keep_within {
+        args.push_str(&format!(" --keep-within {}", shlex::quote(keep_within)));
+    }
+    let rules = [
+        ("hourly", policy.keep_hourly),
+        ("daily", policy.keep_daily),
+        ("weekly", policy.keep_weekly),
+        ("monthly", policy.keep_monthly),
+        ("yearly", policy.keep_yearly),
+    ];
+    for (rule, keep) in rules {
+        if let Some(keep) = keep {
+            args.push_str(&format!(" --keep-{rule} {keep}"));
+        }
+    }
+    args.push_str(&format!(" -- {}", location(config, None)));
+
+    let res = execute_borg(config, &args).await?;
+    check_status(res.status, &res.stderr)?;
+
+    // The decision for every archive is logged because of --list
+    let mut output = PruneOutput { pruned: 0, kept: 0 };
+    for line in String::from_utf8_lossy(&res.stderr).lines() {
+        if let Ok(LoggingMessage::LogMessage { message, .. }) = serde_json::from_str(line) {
+            if message.starts_with("Keeping archive") {
+                output.kept += 1;
+            } else if message.starts_with("Pruning archive") || message.starts_with("Would prune") {
+                output.pruned += 1;
+            }
+        }
+    }
+
+    Ok(output)
+}
@@ -3,6 +3,7 @@
 use std::fs::{metadata, read_to_string};
 use std::os::unix::fs::MetadataExt;
 
+use common::PrunePolicy;
 use log::warn;
 use serde::{Deserialize, Serialize};
 use url::Url;
@@ -22,6 +23,66 @@ pub struct BorgConfig {
     pub repository: String,
     /// The passphrase for the repository
     pub passphrase: String,
+    /// The compression archives are created with, e.g. `zstd,3`
+    ///
+    /// Refer to <https://borgbackup.readthedocs.io/en/stable/usage/help.html#borg-help-compression>
+    /// for the available algorithms.
+    #[serde(default = "default_compression")]
+    pub compression: String,
+}
+
+fn default_compression() -> String {
+    "lz4".to_string()
+}
+
+/// The settings for pruning the archives of the repository
+///
+/// If no rule is set, the retention policy configured in the vinculum is used.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct PruneConfig {
+    /// Prune the repository after every successful archive creation
+    #[serde(default)]
+    pub after_create: bool,
+    /// Keep all archives within this interval, e.g. `7d`
+    pub keep_within: Option<String>,
+    /// The number of hourly archives to keep
+    pub keep_hourly: Option<u16>,
+    /// The number of daily archives to keep
+    pub keep_daily: Option<u16>,
+    /// The number of weekly archives to keep
+    pub keep_weekly: Option<u16>,
+    /// The number of monthly archives to keep
+    pub keep_monthly: Option<u16>,
+    /// The number of yearly archives to keep
+    pub keep_yearly: Option<u16>,
+}
+
+impl PruneConfig {
+    /// The retention policy of the config, if any rule is set
+    pub fn policy(&self) -> Option<PrunePolicy> {
+        let policy = PrunePolicy {
+            keep_within: self.keep_within.clone(),
+            keep_hourly: self.keep_hourly,
+            keep_daily: self.keep_daily,
+            keep_weekly: self.keep_weekly,
+            keep_monthly: self.keep_monthly,
+            keep_yearly: self.keep_yearly,
+        };
+
+        let empty = policy.keep_within.is_none()
+            && [
+                policy.keep_hourly,
+                policy.keep_daily,
+                policy.keep_weekly,
+                policy.keep_monthly,
+                policy.keep_yearly,
+            ]
+            .iter()
+            .all(Option::is_none);
+
+        (!empty).then_some(policy)
+    }
 }
 
 /// The configuration of borg-connect
@@ -44,6 +105,19 @@ pub struct Config {
     pub post_hook: String,
     /// Borg specific configuration
     pub borg: BorgConfig,
+    /// The path to write the results of every run to.
+    ///
+    /// The file is written in the format of the textfile collector of the prometheus
+    /// node exporter, so it should end in `.prom`.
+    pub prometheus_textfile: Option<String>,
+    /// Whether the paths of added and modified files are reported to the vinculum.
+    ///
+    /// The number of added, modified and unchanged files is always reported.
+    #[serde(default)]
+    pub report_changed_paths: bool,
+    /// The settings for pruning the repository
+    #[serde(default)]
+    pub prune: PruneConfig,
 }
 
 impl TryFrom<&str> for Config {
@@ -64,7 +138,14 @@ impl TryFrom<&str> for Config {
 
         let c =
             read_to_string(config_path).map_err(|e| format!("Couldn't read config file: {e}"))?;
-        let config = toml::from_str(&c).map_err(|e| format!("Couldn't deserialize config: {e}"))?;
+        let config: Config =
+            toml::from_str(&c).map_err(|e| format!("Couldn't deserialize config: {e}"))?;
+
+        if let Some(policy) = config.prune.policy() {
+            policy
+                .validate()
+                .map_err(|e| format!("Invalid prune config: {e}"))?;
+        }
 
         Ok(config)
     }
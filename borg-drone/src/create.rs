@@ -4,34 +4,31 @@ use std::ops::Sub;
 use std::time::Instant;
 
 use borgbackup::asynchronous::CreateProgress;
-use borgbackup::common::{CommonOptions, CompressionMode, CreateOptions};
-use borgbackup::output::create::Create;
 use byte_unit::Byte;
-use common::{CreateStats, ErrorReport, State};
-use log::{error, info};
+use common::{BorgWarning, ChangedFiles, CreateStats, ErrorReport, RepositoryTotals, State};
+use log::{error, info, warn};
 use tokio::sync::mpsc;
 
 use crate::api::Api;
+use crate::borg;
+use crate::borg::{create_dry_run, CreateOutput};
 use crate::config::Config;
 
-async fn start_create(
-    options: &CreateOptions,
-    common_options: &CommonOptions,
-) -> Result<Create, ErrorReport> {
-    borgbackup::asynchronous::create(options, common_options)
-        .await
-        .map_err(|err| ErrorReport {
-            state: State::Create,
-            custom: Some(err.to_string()),
-            stdout: None,
-            stderr: None,
-        })
+fn create_error(err: String) -> ErrorReport {
+    ErrorReport {
+        state: State::Create,
+        custom: Some(err),
+        stdout: None,
+        stderr: None,
+        host: None,
+    }
 }
 
-async fn start_create_progress(
-    options: &CreateOptions,
-    common_options: &CommonOptions,
-) -> Result<Create, ErrorReport> {
+async fn start_create(config: &Config) -> Result<CreateOutput, ErrorReport> {
+    borg::create(config, None).await.map_err(create_error)
+}
+
+async fn start_create_progress(config: &Config) -> Result<CreateOutput, ErrorReport> {
     let (tx, mut rx) = mpsc::channel(1);
     tokio::spawn(async move {
         while let Some(CreateProgress::Progress {
@@ -51,67 +48,82 @@ async fn start_create_progress(
         }
     });
 
-    borgbackup::asynchronous::create_progress(options, common_options, tx)
-        .await
-        .map_err(|err| ErrorReport {
-            state: State::Create,
-            custom: Some(err.to_string()),
-            stdout: None,
-            stderr: None,
-        })
+    borg::create(config, Some(tx)).await.map_err(create_error)
 }
 
 /// Create a backup using the settings from [Config].
-pub async fn create(config: &Config, progress: bool) -> Result<CreateStats, ErrorReport> {
+///
+/// The warnings borg logged and the changed files are returned alongside the stats.
+pub async fn create(
+    config: &Config,
+    progress: bool,
+) -> Result<(CreateStats, Vec<BorgWarning>, ChangedFiles), ErrorReport> {
     let start = Instant::now();
 
-    let common_options = CommonOptions {
-        remote_path: config.borg.remote_path.clone(),
-        rsh: Some("ssh -o 'StrictHostKeyChecking accept-new'".to_string()),
-        ..CommonOptions::default()
-    };
-
-    let options = CreateOptions {
-        repository: config.borg.repository.clone(),
-        archive: "{utcnow}".to_string(),
-        passphrase: Some(config.borg.passphrase.clone()),
-        comment: None,
-        compression: Some(CompressionMode::Lz4),
-        paths: vec![],
-        exclude_caches: false,
-        patterns: vec![],
-        pattern_file: Some(config.borg.pattern_file_path.clone()),
-        excludes: vec![],
-        exclude_file: None,
-        numeric_ids: false,
-        sparse: true,
-        read_special: false,
-        no_xattrs: false,
-        no_acls: false,
-        no_flags: false,
-    };
-
-    let stats = if progress {
-        start_create_progress(&options, &common_options).await?
+    let output = if progress {
+        start_create_progress(config).await?
     } else {
-        start_create(&options, &common_options).await?
+        start_create(config).await?
     };
 
     let duration = Instant::now().sub(start);
 
-    Ok(CreateStats {
-        original_size: stats.archive.stats.original_size,
-        compressed_size: stats.archive.stats.compressed_size,
-        deduplicated_size: stats.archive.stats.deduplicated_size,
-        nfiles: stats.archive.stats.nfiles,
-        duration: duration.as_secs(),
-    })
+    if !output.warnings.is_empty() {
+        warn!(
+            "Borg logged {ct} warnings while creating the archive",
+            ct = output.warnings.len()
+        );
+    }
+
+    let archive = output.stats.archive;
+    let stats = archive.stats;
+    let changed_files = ChangedFiles {
+        added: output.added,
+        modified: output.modified,
+        unchanged: stats.nfiles.saturating_sub(output.added + output.modified),
+        paths: config.report_changed_paths.then_some(output.changed_paths),
+        paths_truncated: config.report_changed_paths && output.changed_paths_truncated,
+    };
+    info!(
+        "Files: {added} added, {modified} modified, {unchanged} unchanged",
+        added = changed_files.added,
+        modified = changed_files.modified,
+        unchanged = changed_files.unchanged,
+    );
+
+    Ok((
+        CreateStats {
+            original_size: stats.original_size,
+            compressed_size: stats.compressed_size,
+            deduplicated_size: stats.deduplicated_size,
+            nfiles: stats.nfiles,
+            duration: duration.as_secs(),
+            archive_name: Some(archive.name),
+            archive_id: Some(archive.id),
+            archive_start: Some(archive.start),
+            archive_end: Some(archive.end),
+            repository: output.stats.cache.map(|cache| RepositoryTotals {
+                total_chunks: cache.stats.total_chunks,
+                total_csize: cache.stats.total_csize,
+                total_size: cache.stats.total_size,
+                total_unique_chunks: cache.stats.total_unique_chunks,
+                unique_csize: cache.stats.unique_csize,
+                unique_size: cache.stats.unique_size,
+            }),
+        },
+        output.warnings,
+        changed_files,
+    ))
 }
 
 /// Wrapper for [create].
 ///
 /// This will do the error handling for the create call.
-pub async fn run_create(api: &Api, config: &Config, progress: bool) -> Result<CreateStats, String> {
+pub async fn run_create(
+    api: &Api,
+    config: &Config,
+    progress: bool,
+) -> Result<(CreateStats, Vec<BorgWarning>, ChangedFiles), String> {
     let stats = match create(config, progress).await {
         Ok(stats) => stats,
         Err(err) => {
@@ -125,3 +137,28 @@ pub async fn run_create(api: &Api, config: &Config, progress: bool) -> Result<Cr
 
     Ok(stats)
 }
+
+/// Simulate the creation of an archive and print the paths that would be included.
+///
+/// Fails if borg logged any problems, e.g. because of an invalid pattern file
+/// or missing permissions.
+pub async fn run_create_dry_run(config: &Config) -> Result<(), String> {
+    let output = create_dry_run(config).await?;
+
+    for path in &output.paths {
+        println!("{path}");
+    }
+    info!("{ct} files would be included", ct = output.paths.len());
+
+    if !output.success {
+        for problem in &output.problems {
+            error!("{problem}");
+        }
+        return Err(format!(
+            "Dry run of archive creation failed with {ct} problems",
+            ct = output.problems.len()
+        ));
+    }
+
+    Ok(())
+}
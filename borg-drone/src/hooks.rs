@@ -3,6 +3,7 @@
 use std::ops::Sub;
 use std::time::Instant;
 
+use chrono::Utc;
 use common::{ErrorReport, HookStats, State};
 use log::error;
 use tokio::process::Command;
@@ -10,6 +11,7 @@ use tokio::process::Command;
 use crate::api::Api;
 
 async fn execute_hook(command: &str, state: State) -> Result<HookStats, ErrorReport> {
+    let start_time = Utc::now();
     let start = Instant::now();
 
     let cmd = shlex::split(command).ok_or(ErrorReport {
@@ -17,6 +19,7 @@ async fn execute_hook(command: &str, state: State) -> Result<HookStats, ErrorRep
         custom: Some(format!("Could not split given hook command: {command}")),
         stdout: None,
         stderr: None,
+        host: None,
     })?;
     let Some((cmd, args)) = cmd.split_first() else {
         return Err(ErrorReport {
@@ -24,6 +27,7 @@ async fn execute_hook(command: &str, state: State) -> Result<HookStats, ErrorRep
             custom: Some("hook command was faulty".to_string()),
             stdout: None,
             stderr: None,
+            host: None,
         });
     };
 
@@ -36,6 +40,7 @@ async fn execute_hook(command: &str, state: State) -> Result<HookStats, ErrorRep
             custom: Some(format!("Error spawning command: {e}")),
             stdout: None,
             stderr: None,
+            host: None,
         })?;
 
     if !out.status.success() {
@@ -50,12 +55,15 @@ async fn execute_hook(command: &str, state: State) -> Result<HookStats, ErrorRep
             )),
             stdout: Some(stdout),
             stderr: Some(stderr),
+            host: None,
         });
     }
 
     let duration = Instant::now().sub(start);
     Ok(HookStats {
         duration: duration.as_secs(),
+        start: Some(start_time),
+        end: Some(Utc::now()),
     })
 }
 
@@ -0,0 +1,51 @@
+//! The inventory of the host the drone runs on
+
+use std::fs::read_to_string;
+
+use common::HostInventory;
+use log::warn;
+
+use crate::borg::version;
+use crate::config::Config;
+
+/// Read a single value from a file, e.g. in `/proc`
+fn read_value(path: &str) -> Option<String> {
+    read_to_string(path)
+        .ok()
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+}
+
+/// Retrieve the name of the operating system from the os-release file
+fn os_name() -> Option<String> {
+    let os_release = read_to_string("/etc/os-release")
+        .or_else(|_| read_to_string("/usr/lib/os-release"))
+        .ok()?;
+
+    os_release.lines().find_map(|line| {
+        line.strip_prefix("PRETTY_NAME=")
+            .map(|x| x.trim_matches('"').to_string())
+    })
+}
+
+/// Collect the inventory of the host.
+///
+/// Values that can't be determined are left out.
+pub async fn collect_inventory(config: &Config) -> HostInventory {
+    let borg_version = match version(config).await {
+        Ok(version) => Some(version),
+        Err(err) => {
+            warn!("Could not retrieve the version of borg: {err}");
+            None
+        }
+    };
+
+    HostInventory {
+        hostname: read_value("/proc/sys/kernel/hostname"),
+        os: os_name(),
+        kernel: read_value("/proc/sys/kernel/osrelease"),
+        borg_version,
+        drone_version: env!("CARGO_PKG_VERSION").to_string(),
+        compression: config.borg.compression.clone(),
+    }
+}
@@ -5,19 +5,32 @@
 
 use std::env;
 
+use chrono::Utc;
 use clap::{ArgAction, Parser, Subcommand};
-use common::State;
+use common::borg::CheckMode;
+use common::{StatReport, State};
 use log::{debug, info, warn};
 
 use crate::api::Api;
 use crate::config::Config;
-use crate::create::run_create;
+use crate::create::{run_create, run_create_dry_run};
 use crate::hooks::run_hook;
+use crate::inventory::collect_inventory;
+use crate::prune::run_prune;
+use crate::repository::{run_check, run_info, run_list};
+use crate::restore::run_restore;
+use crate::textfile::{write_operation_textfile, write_textfile};
 
 pub mod api;
+pub mod borg;
 pub mod config;
 pub mod create;
 pub mod hooks;
+pub mod inventory;
+pub mod prune;
+pub mod repository;
+pub mod restore;
+pub mod textfile;
 
 /// The available commands for borg-connect
 #[derive(Subcommand)]
@@ -26,7 +39,9 @@ pub enum Command {
     Create {
         /// Run the backup as dry run.
         ///
-        /// This will execute the pre and post hooks, but will skip the creation of the backup.
+        /// This will execute the pre and post hooks and list the files that would be archived,
+        /// but will skip the creation of the backup.
+        /// Problems like an invalid pattern file or unreadable files fail the run.
         #[clap(long, default_value_t = false)]
         dry_run: bool,
 
@@ -38,6 +53,87 @@ pub enum Command {
         #[clap(short = 'R', long, default_value_t = false)]
         dont_report: bool,
     },
+    /// Check the integrity of the repository
+    ///
+    /// A failed check is reported to the vinculum.
+    Check {
+        /// Only check the consistency of the repository, not of the archives
+        #[clap(long, default_value_t = false, conflicts_with = "verify_data")]
+        repository_only: bool,
+
+        /// Verify the integrity of the data by reading it
+        #[clap(long, default_value_t = false)]
+        verify_data: bool,
+
+        /// Only check the repository partially for this number of seconds.
+        ///
+        /// The next check continues where the previous one stopped.
+        #[clap(long, requires = "repository_only")]
+        max_duration: Option<u64>,
+
+        /// Print the result as json
+        #[clap(long, default_value_t = false)]
+        json: bool,
+
+        /// Do not report a failed check to the vinculum
+        #[clap(short = 'R', long, default_value_t = false)]
+        dont_report: bool,
+    },
+    /// List the archives in the repository
+    List {
+        /// Print the archives as json
+        #[clap(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Show information about the repository
+    Info {
+        /// Print the information as json
+        #[clap(long, default_value_t = false)]
+        json: bool,
+    },
+    /// Prune the archives of the repository
+    ///
+    /// The retention policy is taken from the config, or from the vinculum,
+    /// if the config contains no rules.
+    Prune {
+        /// Only list the archives that would be pruned
+        #[clap(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// Do not report the results to the vinculum
+        #[clap(short = 'R', long, default_value_t = false)]
+        dont_report: bool,
+    },
+    /// Restore an archive into a directory
+    ///
+    /// The restore is reported to the vinculum.
+    Restore {
+        /// The directory to extract the archive into
+        target: String,
+
+        /// Only restore these paths of the archive
+        paths: Vec<String>,
+
+        /// The name of the archive to restore, defaults to the latest archive
+        #[clap(short = 'a', long)]
+        archive: Option<String>,
+
+        /// Allow restoring into the root directory
+        #[clap(long, default_value_t = false)]
+        force: bool,
+
+        /// Output the progress while restoring
+        #[clap(short = 'p', long, default_value_t = false)]
+        progress: bool,
+
+        /// Only list the items that would be restored
+        #[clap(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// Do not report the restore to the vinculum
+        #[clap(short = 'R', long, default_value_t = false)]
+        dont_report: bool,
+    },
 }
 
 /// A helper utility for integrating borg in the vinculum.
@@ -75,47 +171,220 @@ async fn main() -> Result<(), String> {
     let config = Config::try_from(cli.config_path.as_str())?;
 
     match cli.command {
-        Command::Create { dry_run, progress, dont_report } => {
+        Command::Create {
+            dry_run,
+            progress,
+            dont_report,
+        } => {
             debug!("Initializing API");
-            let api = Api::new(config.vinculum_address.clone(), &config.vinculum_token)?;
+            let api = Api::new(
+                config.vinculum_address.clone(),
+                &config.vinculum_token,
+                collect_inventory(&config).await,
+            )?;
 
-            let mut pre_hook_stats = None;
-            let mut create_stats = None;
-            let mut post_hook_stats = None;
-
-            if config.pre_hook.is_empty() {
-                info!("Skipping pre hook");
-            } else {
-                info!("Starting pre hook");
-                pre_hook_stats = Some(run_hook(&api, &config.pre_hook, State::PreHook).await?);
-                info!("Finished pre hook");
-            }
+            let result = create_archive(&api, &config, dry_run, progress).await;
 
             if !dry_run {
-                info!("Starting archive creation");
-                create_stats = Some(run_create(&api, &config, progress).await?);
-                info!("Finished archive creation");
-            } else {
-                info!("Skipping archive creation");
-            }
+                if let Some(path) = &config.prometheus_textfile {
+                    let textfile_result = match &result {
+                        Ok(Some(report)) => Some(Ok(report)),
+                        Ok(None) => None,
+                        Err((state, _)) => Some(Err(*state)),
+                    };
 
-            if config.post_hook.is_empty() {
-                info!("Skipping post hook");
-            } else {
-                info!("Starting post hook");
-                post_hook_stats = Some(run_hook(&api, &config.post_hook, State::PostHook).await?);
-                info!("Finished post hook");
+                    if let Some(textfile_result) = textfile_result {
+                        info!("Writing prometheus textfile");
+                        if let Err(err) = write_textfile(path, textfile_result) {
+                            warn!("Error while writing prometheus textfile: {err}");
+                        }
+                    }
+                }
             }
 
-            if !dry_run && !dont_report {
-                if let Some(cs) = create_stats {
+            let report = result.map_err(|(_, err)| err)?;
+
+            if !dont_report {
+                if let Some(report) = report {
                     info!("Send report to vinculum");
-                    api.send_stats(pre_hook_stats, cs, post_hook_stats).await?;
+                    api.send_stats(&report).await?;
                     info!("Report was sent successfully");
                 }
             }
+
+            if !dry_run && config.prune.after_create {
+                let result = run_prune(&api, &config, false, !dont_report).await;
+                write_operation_result(&config, State::Prune, result.is_ok());
+                result?;
+            }
+        }
+        Command::Prune {
+            dry_run,
+            dont_report,
+        } => {
+            debug!("Initializing API");
+            let api = Api::new(
+                config.vinculum_address.clone(),
+                &config.vinculum_token,
+                collect_inventory(&config).await,
+            )?;
+
+            let result = run_prune(&api, &config, dry_run, !dont_report).await;
+            if !dry_run {
+                write_operation_result(&config, State::Prune, result.is_ok());
+            }
+            result?;
+        }
+        Command::Check {
+            repository_only,
+            verify_data,
+            max_duration,
+            json,
+            dont_report,
+        } => {
+            let mode = if repository_only {
+                CheckMode::RepositoryOnly
+            } else if verify_data {
+                CheckMode::VerifyData
+            } else {
+                CheckMode::Full
+            };
+
+            let api = if dont_report {
+                None
+            } else {
+                debug!("Initializing API");
+                Some(Api::new(
+                    config.vinculum_address.clone(),
+                    &config.vinculum_token,
+                    collect_inventory(&config).await,
+                )?)
+            };
+
+            let result = run_check(api.as_ref(), &config, mode, max_duration, json).await;
+            write_operation_result(&config, State::Check, result.is_ok());
+            result?;
+        }
+        Command::List { json } => run_list(&config, json).await?,
+        Command::Info { json } => run_info(&config, json).await?,
+        Command::Restore {
+            target,
+            paths,
+            archive,
+            force,
+            progress,
+            dry_run,
+            dont_report,
+        } => {
+            let api = if dont_report || dry_run {
+                None
+            } else {
+                debug!("Initializing API");
+                Some(Api::new(
+                    config.vinculum_address.clone(),
+                    &config.vinculum_token,
+                    collect_inventory(&config).await,
+                )?)
+            };
+
+            run_restore(
+                api.as_ref(),
+                &config,
+                archive,
+                &target,
+                &paths,
+                force,
+                progress,
+                dry_run,
+            )
+            .await?;
         }
     }
 
     Ok(())
 }
+
+/// Write the outcome of a check or prune to the prometheus textfile, if it is configured
+fn write_operation_result(config: &Config, state: State, success: bool) {
+    if let Some(path) = &config.prometheus_textfile {
+        info!("Writing prometheus textfile");
+        if let Err(err) = write_operation_textfile(path, state, success) {
+            warn!("Error while writing prometheus textfile: {err}");
+        }
+    }
+}
+
+/// Run the hooks and the archive creation.
+///
+/// If the archive was created, the collected stats are returned.
+/// On error, the state the error occurred in is returned alongside the error.
+async fn create_archive(
+    api: &Api,
+    config: &Config,
+    dry_run: bool,
+    progress: bool,
+) -> Result<Option<StatReport>, (State, String)> {
+    let start = Utc::now();
+    let mut pre_hook_stats = None;
+    let mut create_result = None;
+    let mut post_hook_stats = None;
+    let mut dry_run_result = Ok(());
+
+    if config.pre_hook.is_empty() {
+        info!("Skipping pre hook");
+    } else {
+        info!("Starting pre hook");
+        pre_hook_stats = Some(
+            run_hook(api, &config.pre_hook, State::PreHook)
+                .await
+                .map_err(|e| (State::PreHook, e))?,
+        );
+        info!("Finished pre hook");
+    }
+
+    if !dry_run {
+        info!("Starting archive creation");
+        create_result = Some(
+            run_create(api, config, progress)
+                .await
+                .map_err(|e| (State::Create, e))?,
+        );
+        info!("Finished archive creation");
+    } else {
+        info!("Starting dry run of archive creation");
+        // The post hook is executed on a failed dry run as well,
+        // as it may clean up after the pre hook
+        dry_run_result = run_create_dry_run(config)
+            .await
+            .map_err(|e| (State::Create, e));
+        info!("Finished dry run of archive creation");
+    }
+
+    if config.post_hook.is_empty() {
+        info!("Skipping post hook");
+    } else {
+        info!("Starting post hook");
+        post_hook_stats = Some(
+            run_hook(api, &config.post_hook, State::PostHook)
+                .await
+                .map_err(|e| (State::PostHook, e))?,
+        );
+        info!("Finished post hook");
+    }
+
+    dry_run_result?;
+
+    Ok(
+        create_result.map(|(create_stats, warnings, changed_files)| StatReport {
+            pre_hook_stats,
+            create_stats,
+            post_hook_stats,
+            warnings,
+            changed_files: Some(changed_files),
+            start: Some(start),
+            end: Some(Utc::now()),
+            sent_at: None,
+            host: None,
+        }),
+    )
+}
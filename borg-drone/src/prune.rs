@@ -0,0 +1,119 @@
+//! Pruning of the archives in the repository
+
+use std::ops::Sub;
+use std::time::Instant;
+
+use borgbackup::common::CompactOptions;
+use common::{ErrorReport, PrunePolicy, PruneStats, State};
+use log::{error, info};
+
+use crate::api::Api;
+use crate::borg::{common_options, prune};
+use crate::config::Config;
+
+/// Retrieve the retention policy.
+///
+/// The policy of the config takes precedence over the policy configured in the vinculum.
+async fn retention_policy(api: &Api, config: &Config) -> Result<PrunePolicy, String> {
+    if let Some(policy) = config.prune.policy() {
+        info!("Using retention policy of the config");
+        return Ok(policy);
+    }
+
+    info!("Retrieving retention policy from vinculum");
+    let policy = api
+        .get_prune_policy()
+        .await?
+        .ok_or("No retention policy is configured, neither in the config nor in the vinculum")?;
+    policy
+        .validate()
+        .map_err(|e| format!("Invalid retention policy in vinculum: {e}"))?;
+
+    Ok(policy)
+}
+
+/// Prune the archives and free the space in the repository
+async fn prune_repository(api: &Api, config: &Config, dry_run: bool) -> Result<PruneStats, String> {
+    let policy = retention_policy(api, config).await?;
+
+    let start = Instant::now();
+
+    let output = prune(config, &policy, dry_run).await?;
+
+    // Since borg 1.2 the space of pruned archives is only freed by compacting
+    if !dry_run && output.pruned > 0 {
+        info!("Compacting repository");
+        borgbackup::asynchronous::compact(
+            &CompactOptions {
+                repository: config.borg.repository.clone(),
+            },
+            &common_options(config),
+        )
+        .await
+        .map_err(|e| format!("Could not compact repository: {e}"))?;
+    }
+
+    let duration = Instant::now().sub(start);
+
+    Ok(PruneStats {
+        pruned: output.pruned,
+        kept: output.kept,
+        duration: duration.as_secs(),
+        host: None,
+    })
+}
+
+/// Prune the repository according to the retention policy.
+///
+/// Errors are reported to the vinculum.
+/// Unless `report` is false or this is a dry run, the stats are reported as well.
+pub async fn run_prune(
+    api: &Api,
+    config: &Config,
+    dry_run: bool,
+    report: bool,
+) -> Result<(), String> {
+    info!("Starting prune");
+    let stats = match prune_repository(api, config, dry_run).await {
+        Ok(stats) => stats,
+        Err(err) => {
+            error!("Error while pruning repository: {err}");
+            if report {
+                let error_report = ErrorReport {
+                    state: State::Prune,
+                    custom: Some(err.clone()),
+                    stdout: None,
+                    stderr: None,
+                    host: None,
+                };
+                if let Err(err) = api.send_error(error_report).await {
+                    error!("Error while sending error to vinculum: {err}");
+                }
+            }
+            return Err(err);
+        }
+    };
+
+    if dry_run {
+        info!(
+            "Would prune {pruned} archives, keeping {kept}",
+            pruned = stats.pruned,
+            kept = stats.kept
+        );
+        return Ok(());
+    }
+
+    info!(
+        "Pruned {pruned} archives, kept {kept}",
+        pruned = stats.pruned,
+        kept = stats.kept
+    );
+
+    if report {
+        info!("Send report to vinculum");
+        api.send_prune(stats).await?;
+        info!("Report was sent successfully");
+    }
+
+    Ok(())
+}
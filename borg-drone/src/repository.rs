@@ -0,0 +1,140 @@
+//! Inspection of the repository: checks, listing of archives and information
+
+use borgbackup::common::ListOptions;
+use byte_unit::Byte;
+use common::borg::{check_repository, info_repository, CheckMode};
+use common::{ErrorReport, State};
+use log::{error, info};
+use serde::Serialize;
+
+use crate::api::Api;
+use crate::borg::common_options;
+use crate::config::Config;
+
+/// Print a value as pretty json
+fn print_json(value: &impl Serialize) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Could not serialize output: {e}"))?;
+    println!("{json}");
+    Ok(())
+}
+
+/// Format a size in bytes for humans
+fn format_size(size: u64) -> String {
+    Byte::from(size as u128)
+        .get_appropriate_unit(false)
+        .to_string()
+}
+
+/// Check the repository and print the result.
+///
+/// If the check fails, the failure is reported to the vinculum, unless `api` is `None`.
+pub async fn run_check(
+    api: Option<&Api>,
+    config: &Config,
+    mode: CheckMode,
+    max_duration: Option<u64>,
+    json: bool,
+) -> Result<(), String> {
+    info!("Checking repository");
+    let output = check_repository(
+        &common_options(config),
+        &config.borg.repository,
+        &config.borg.passphrase,
+        mode,
+        max_duration,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if json {
+        print_json(&output)?;
+    } else if output.success {
+        println!("No problems found");
+    } else {
+        println!("Problems found:");
+        for problem in &output.problems {
+            println!("{problem}");
+        }
+    }
+
+    if output.success {
+        return Ok(());
+    }
+
+    if let Some(api) = api {
+        let report = ErrorReport {
+            state: State::Check,
+            custom: Some(output.problems.join("\n")),
+            stdout: None,
+            stderr: None,
+            host: None,
+        };
+        if let Err(err) = api.send_error(report).await {
+            error!("Error while sending error to vinculum: {err}");
+        }
+    }
+
+    Err("The check of the repository failed".to_string())
+}
+
+/// List the archives of the repository
+pub async fn run_list(config: &Config, json: bool) -> Result<(), String> {
+    let list = borgbackup::asynchronous::list(
+        &ListOptions {
+            repository: config.borg.repository.clone(),
+            passphrase: Some(config.borg.passphrase.clone()),
+        },
+        &common_options(config),
+    )
+    .await
+    .map_err(|e| format!("Could not list archives: {e}"))?;
+
+    if json {
+        return print_json(&list);
+    }
+
+    for archive in list.archives {
+        println!(
+            "{name:<40} {start} {id}",
+            name = archive.name,
+            start = archive.start,
+            id = archive.id
+        );
+    }
+
+    Ok(())
+}
+
+/// Print information about the repository
+pub async fn run_info(config: &Config, json: bool) -> Result<(), String> {
+    let info = info_repository(
+        &common_options(config),
+        &config.borg.repository,
+        &config.borg.passphrase,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if json {
+        return print_json(&info);
+    }
+
+    let stats = info.cache.stats;
+    println!("Repository ID: {}", info.repository.id);
+    println!("Location: {}", info.repository.location);
+    println!("Last modified: {}", info.repository.last_modified);
+    if let Some(encryption) = info.encryption {
+        println!("Encryption: {:?}", encryption.mode);
+    }
+    println!("Original size: {}", format_size(stats.total_size));
+    println!("Compressed size: {}", format_size(stats.total_csize));
+    println!("Deduplicated size: {}", format_size(stats.unique_csize));
+    println!(
+        "Chunks: {unique} unique, {total} total",
+        unique = stats.total_unique_chunks,
+        total = stats.total_chunks
+    );
+
+    Ok(())
+}
@@ -0,0 +1,186 @@
+//! Restoring of archives from the repository
+
+use std::fs;
+use std::ops::Sub;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use borgbackup::common::ListOptions;
+use byte_unit::Byte;
+use common::borg::list_archive_items;
+use common::RestoreReport;
+use log::{error, info};
+use tokio::sync::mpsc;
+
+use crate::api::Api;
+use crate::borg::{common_options, extract, ExtractProgress};
+use crate::config::Config;
+
+/// Retrieve the name of the latest archive in the repository
+async fn latest_archive(config: &Config) -> Result<String, String> {
+    let list = borgbackup::asynchronous::list(
+        &ListOptions {
+            repository: config.borg.repository.clone(),
+            passphrase: Some(config.borg.passphrase.clone()),
+        },
+        &common_options(config),
+    )
+    .await
+    .map_err(|e| format!("Could not list archives: {e}"))?;
+
+    list.archives
+        .into_iter()
+        .max_by_key(|archive| archive.start)
+        .map(|archive| archive.name)
+        .ok_or_else(|| "The repository does not contain any archives".to_string())
+}
+
+/// Resolve the target directory of a restore.
+///
+/// The directory is created, if it doesn't exist yet.
+/// The root directory is refused unless `force` is set.
+fn resolve_target(target: &str, force: bool, create: bool) -> Result<PathBuf, String> {
+    let target = Path::new(target);
+
+    if create {
+        fs::create_dir_all(target).map_err(|e| {
+            format!(
+                "Could not create target directory {}: {e}",
+                target.display()
+            )
+        })?;
+    }
+
+    let target = if target.exists() {
+        target
+            .canonicalize()
+            .map_err(|e| format!("Could not resolve target {}: {e}", target.display()))?
+    } else {
+        target.to_path_buf()
+    };
+
+    if target == Path::new("/") && !force {
+        return Err("Refusing to restore into /, use --force to do it anyway".to_string());
+    }
+
+    if target.exists() && !target.is_dir() {
+        return Err(format!("Target {} is not a directory", target.display()));
+    }
+
+    Ok(target)
+}
+
+/// Extract the archive while logging the progress
+async fn start_extract_progress(
+    config: &Config,
+    archive: &str,
+    paths: &[String],
+    target: &Path,
+) -> Result<(), String> {
+    let (tx, mut rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        while let Some(ExtractProgress {
+            current,
+            total,
+            path,
+        }) = rx.recv().await
+        {
+            info!(
+                "{c} / {t}, Path: {path}",
+                c = Byte::from(current as u128).get_appropriate_unit(false),
+                t = Byte::from(total as u128).get_appropriate_unit(false),
+            )
+        }
+    });
+
+    extract(config, archive, paths, target, Some(tx)).await
+}
+
+/// Restore an archive into `target`.
+///
+/// If `archive` is `None`, the latest archive is restored.
+/// If `paths` is not empty, only the given paths are restored.
+/// On a dry run, the items that would be restored are printed instead.
+///
+/// Unless `api` is `None`, the restore is reported to the vinculum.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_restore(
+    api: Option<&Api>,
+    config: &Config,
+    archive: Option<String>,
+    target: &str,
+    paths: &[String],
+    force: bool,
+    progress: bool,
+    dry_run: bool,
+) -> Result<(), String> {
+    let archive = match archive {
+        Some(archive) => archive,
+        None => latest_archive(config).await?,
+    };
+    // Paths in archives are stored without a leading slash
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|x| x.trim_start_matches('/').to_string())
+        .collect();
+
+    let target = resolve_target(target, force, !dry_run)?;
+
+    if dry_run {
+        info!("Listing items of archive {archive}");
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+        let items = list_archive_items(
+            &common_options(config),
+            &config.borg.repository,
+            &archive,
+            &config.borg.passphrase,
+            &paths,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        for item in items {
+            println!(
+                "{kind} {size:>12} {path}",
+                kind = item.kind,
+                size = item.size,
+                path = target.join(&item.path).display()
+            );
+        }
+        return Ok(());
+    }
+
+    info!(
+        "Restoring archive {archive} into {target}",
+        target = target.display()
+    );
+    let start = Instant::now();
+    let result = if progress {
+        start_extract_progress(config, &archive, &paths, &target).await
+    } else {
+        extract(config, &archive, &paths, &target, None).await
+    };
+    let duration = Instant::now().sub(start);
+
+    match &result {
+        Ok(_) => info!("Finished restore"),
+        Err(err) => error!("Error while restoring archive: {err}"),
+    }
+
+    if let Some(api) = api {
+        let report = RestoreReport {
+            archive,
+            paths,
+            target: target.display().to_string(),
+            success: result.is_ok(),
+            error: result.as_ref().err().cloned(),
+            duration: duration.as_secs(),
+            host: None,
+        };
+        info!("Send report to vinculum");
+        if let Err(err) = api.send_restore(&report).await {
+            error!("Error while sending report to vinculum: {err}");
+        }
+    }
+
+    result
+}
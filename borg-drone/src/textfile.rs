@@ -0,0 +1,263 @@
+//! Writing the results of a run in the prometheus textfile collector format
+
+use std::fmt::Write;
+use std::fs::{read_to_string, rename, write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::{StatReport, State};
+
+/// The prefix of all metrics that describe the last successful run
+const SUCCESS_PREFIX: &str = "borg_drone_last_success_";
+/// The prefix of all metrics that describe the last failed run
+const ERROR_PREFIX: &str = "borg_drone_last_error_";
+/// The prefixes of all metrics that describe the last check and prune of the repository
+const OPERATION_PREFIXES: [&str; 2] = ["borg_drone_last_check_", "borg_drone_last_prune_"];
+
+fn write_metric(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    // Writing to a string can't fail
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+/// Retrieve the name of the metric a line of a textfile belongs to
+fn metric_name(line: &str) -> Option<&str> {
+    match line.strip_prefix("# ") {
+        Some(comment) => comment.split(' ').nth(1),
+        None => line.split(['{', ' ']).next(),
+    }
+}
+
+fn state_label(state: State) -> &'static str {
+    match state {
+        State::PreHook => "pre_hook",
+        State::Create => "create",
+        State::PostHook => "post_hook",
+        State::Check => "check",
+        State::Prune => "prune",
+    }
+}
+
+/// Write the result of a run to the textfile at `path`.
+///
+/// The metrics of the last successful or failed run are kept from the existing file,
+/// depending on the outcome of this run, as well as the metrics of checks and prunes.
+/// The file is replaced atomically, so the collector never reads a partially written file.
+pub fn write_textfile(path: &str, result: Result<&StatReport, State>) -> Result<(), String> {
+    let now = unix_timestamp()?;
+
+    let mut out = String::new();
+
+    write_metric(
+        &mut out,
+        "borg_drone_last_run_timestamp_seconds",
+        "The point in time the last run finished",
+        now,
+    );
+    write_metric(
+        &mut out,
+        "borg_drone_last_run_success",
+        "Whether the last run was successful",
+        result.is_ok() as u8,
+    );
+
+    let keep_prefix = match result {
+        Ok(report) => {
+            write_metric(
+                &mut out,
+                "borg_drone_last_success_timestamp_seconds",
+                "The point in time the last successful run finished",
+                now,
+            );
+            if let Some(pre) = report.pre_hook_stats {
+                write_metric(
+                    &mut out,
+                    "borg_drone_last_success_pre_hook_duration_seconds",
+                    "The duration of the pre hook",
+                    pre.duration,
+                );
+            }
+            write_metric(
+                &mut out,
+                "borg_drone_last_success_create_duration_seconds",
+                "The duration of the archive creation",
+                report.create_stats.duration,
+            );
+            if let Some(post) = report.post_hook_stats {
+                write_metric(
+                    &mut out,
+                    "borg_drone_last_success_post_hook_duration_seconds",
+                    "The duration of the post hook",
+                    post.duration,
+                );
+            }
+            write_metric(
+                &mut out,
+                "borg_drone_last_success_original_size_bytes",
+                "The original size of the archive",
+                report.create_stats.original_size,
+            );
+            write_metric(
+                &mut out,
+                "borg_drone_last_success_compressed_size_bytes",
+                "The compressed size of the archive",
+                report.create_stats.compressed_size,
+            );
+            write_metric(
+                &mut out,
+                "borg_drone_last_success_deduplicated_size_bytes",
+                "The deduplicated size of the archive",
+                report.create_stats.deduplicated_size,
+            );
+            write_metric(
+                &mut out,
+                "borg_drone_last_success_files",
+                "The number of files in the archive",
+                report.create_stats.nfiles,
+            );
+            write_metric(
+                &mut out,
+                "borg_drone_last_success_warnings",
+                "The number of warnings borg logged while creating the archive",
+                report.warnings.len(),
+            );
+
+            ERROR_PREFIX
+        }
+        Err(state) => {
+            write_metric(
+                &mut out,
+                "borg_drone_last_error_timestamp_seconds",
+                "The point in time the last failed run finished",
+                now,
+            );
+            let _ = writeln!(
+                out,
+                "# HELP borg_drone_last_error_state The state the last failed run failed in"
+            );
+            let _ = writeln!(out, "# TYPE borg_drone_last_error_state gauge");
+            let _ = writeln!(
+                out,
+                r#"borg_drone_last_error_state{{state="{state}"}} 1"#,
+                state = state_label(state)
+            );
+
+            SUCCESS_PREFIX
+        }
+    };
+
+    replace_textfile(path, out, |name| {
+        name.starts_with(keep_prefix) || OPERATION_PREFIXES.iter().any(|x| name.starts_with(x))
+    })
+}
+
+/// Write the result of a check or prune of the repository to the textfile at `path`.
+///
+/// `state` must be [State::Check] or [State::Prune].
+/// The metrics of all other runs are kept from the existing file.
+pub fn write_operation_textfile(path: &str, state: State, success: bool) -> Result<(), String> {
+    let operation = state_label(state);
+    let prefix = format!("borg_drone_last_{operation}_");
+
+    let mut out = String::new();
+    write_metric(
+        &mut out,
+        &format!("{prefix}timestamp_seconds"),
+        &format!("The point in time the last {operation} finished"),
+        unix_timestamp()?,
+    );
+    write_metric(
+        &mut out,
+        &format!("{prefix}success"),
+        &format!("Whether the last {operation} was successful"),
+        success as u8,
+    );
+
+    replace_textfile(path, out, |name| !name.starts_with(&prefix))
+}
+
+/// The current point in time as seconds since the unix epoch
+fn unix_timestamp() -> Result<u64, String> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| format!("Invalid system time: {e}"))?
+        .as_secs())
+}
+
+/// Replace the textfile at `path` with `out` and the lines of the existing file
+/// whose metric name matches `keep`.
+///
+/// The file is replaced atomically, so the collector never reads a partially written file.
+fn replace_textfile(
+    path: &str,
+    mut out: String,
+    keep: impl Fn(&str) -> bool,
+) -> Result<(), String> {
+    // A missing or unreadable file is treated as empty
+    if let Ok(existing) = read_to_string(path) {
+        for line in existing.lines() {
+            if metric_name(line).is_some_and(&keep) {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+
+    let tmp_path = format!("{path}.tmp");
+    write(&tmp_path, out).map_err(|e| format!("Could not write {tmp_path}: {e}"))?;
+    rename(&tmp_path, path).map_err(|e| format!("Could not move {tmp_path} to {path}: {e}"))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metric_name_of_samples() {
+        assert_eq!(
+            metric_name("borg_drone_last_success_timestamp_seconds 1700000000"),
+            Some("borg_drone_last_success_timestamp_seconds")
+        );
+        assert_eq!(
+            metric_name(r#"borg_drone_last_error_state{state="create"} 1"#),
+            Some("borg_drone_last_error_state")
+        );
+    }
+
+    #[test]
+    fn metric_name_of_comments() {
+        assert_eq!(
+            metric_name("# HELP borg_drone_last_error_state The state the run failed in"),
+            Some("borg_drone_last_error_state")
+        );
+        assert_eq!(
+            metric_name("# TYPE borg_drone_last_success_nfiles gauge"),
+            Some("borg_drone_last_success_nfiles")
+        );
+        assert_eq!(metric_name("# comment"), None);
+    }
+
+    #[test]
+    fn other_runs_are_kept() {
+        let path = std::env::temp_dir().join(format!(
+            "borg-drone-textfile-{pid}.prom",
+            pid = std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+
+        write_operation_textfile(path, State::Check, false).unwrap();
+        write_textfile(path, Err(State::Create)).unwrap();
+        write_operation_textfile(path, State::Prune, true).unwrap();
+        write_operation_textfile(path, State::Check, true).unwrap();
+
+        let content = read_to_string(path).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert!(content.contains("borg_drone_last_run_success 0\n"));
+        assert!(content.contains("borg_drone_last_prune_success 1\n"));
+        assert!(content.contains("borg_drone_last_check_success 1\n"));
+        assert!(!content.contains("borg_drone_last_check_success 0\n"));
+    }
+}
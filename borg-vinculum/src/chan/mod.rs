@@ -1,14 +1,267 @@
-use common::ErrorReport;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use common::{BorgWarning, ErrorReport};
 use log::{info, warn};
 use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::Sender;
 
 use crate::config::Config;
 use crate::models::Drone;
 use crate::modules::matrix::{MatrixApi, MatrixError};
 
+/// The maximum number of borg warnings that are included in a notification
+const MAX_NOTIFIED_WARNINGS: usize = 10;
+
+/// A notification that is sent by the matrix notifier
+pub enum Notification {
+    /// A drone reported an error
+    Error {
+        /// The drone that reported the error
+        drone: Drone,
+        /// The reported error
+        report: ErrorReport,
+    },
+    /// The integrity check of a repository failed
+    CheckFailed {
+        /// The drone whose repository was checked
+        drone: Drone,
+        /// The output of borg
+        output: String,
+    },
+    /// A restore drill failed
+    RestoreDrillFailed {
+        /// The drone whose repository was tested
+        drone: Drone,
+        /// The archive the files were restored from
+        archive: Option<String>,
+        /// The error that occurred
+        error: String,
+    },
+    /// Borg logged warnings while a drone created an archive
+    ///
+    /// The archive was created, but e.g. some files could not be read.
+    BorgWarnings {
+        /// The drone that created the archive
+        drone: Drone,
+        /// The logged warnings, the message contains at most [MAX_NOTIFIED_WARNINGS] of them
+        warnings: Vec<BorgWarning>,
+    },
+    /// A drone reported a different hostname than before
+    ///
+    /// This may indicate that the token of the drone leaked.
+    HostnameChanged {
+        /// The affected drone
+        drone: Drone,
+        /// The previously reported hostname
+        previous: String,
+        /// The newly reported hostname
+        current: String,
+    },
+    /// The vinculum detected something suspicious about a drone
+    Warning {
+        /// The affected drone
+        drone: Drone,
+        /// The description of the warning
+        message: String,
+    },
+}
+
+impl Notification {
+    /// Format the notification as plain and html message
+    fn format(&self) -> (String, Option<String>) {
+        match self {
+            Notification::Error { drone, report } => {
+                let msg = format!(
+                    r#"🚨 The vinculum reports alarm for drone {drone_name}!
+                
+                {drone_name} failed in {state}
+                
+                {custom}{stderr}{stdout}"#,
+                    drone_name = drone.name.clone(),
+                    state = report.state,
+                    custom = report
+                        .custom
+                        .as_ref()
+                        .map_or("".to_string(), |x| format!("Custom error:\n{x}\n\n")),
+                    stderr = report
+                        .stderr
+                        .as_ref()
+                        .map_or("".to_string(), |x| format!("Stderr:\n{x}\n\n")),
+                    stdout = report
+                        .stdout
+                        .as_ref()
+                        .map_or("".to_string(), |x| format!("Stdout:\n{x}")),
+                );
+                let formatted_msg = Some(format!(
+                    r#"<h4>🚨 The vinculum reports alarm for drone <font color="cyan">{drone_name}</font>!</h4>
+                <p><font color="cyan">{drone_name}</font> failed in {state}</p>
+                {custom}
+                {stderr}
+                {stdout}
+            "#,
+                    drone_name = drone.name.clone(),
+                    state = report.state,
+                    custom = report.custom.as_ref().map_or("".to_string(), |x| format!(
+                        "<p>Custom error:<br><code>{x}</code></p>"
+                    )),
+                    stderr = report.stderr.as_ref().map_or("".to_string(), |x| format!(
+                        "<p>Stderr:<br><pre>{x}</pre></p>"
+                    )),
+                    stdout = report.stdout.as_ref().map_or("".to_string(), |x| format!(
+                        "<p>Stdout:<br><pre>{x}</pre></p>"
+                    )),
+                ));
+
+                (msg, formatted_msg)
+            }
+            Notification::CheckFailed { drone, output } => {
+                let msg = format!(
+                    r#"🚨 The vinculum reports alarm for drone {drone_name}!
+                
+                The integrity check of the repository failed:
+                {output}"#,
+                    drone_name = drone.name,
+                );
+                let formatted_msg = Some(format!(
+                    r#"<h4>🚨 The vinculum reports alarm for drone <font color="cyan">{drone_name}</font>!</h4>
+                <p>The integrity check of the repository failed:<br><pre>{output}</pre></p>"#,
+                    drone_name = drone.name,
+                ));
+
+                (msg, formatted_msg)
+            }
+            Notification::RestoreDrillFailed {
+                drone,
+                archive,
+                error,
+            } => {
+                let archive = archive
+                    .as_ref()
+                    .map_or("".to_string(), |x| format!(" from archive {x}"));
+                let msg = format!(
+                    r#"🚨 The vinculum reports alarm for drone {drone_name}!
+                
+                The restore drill{archive} failed:
+                {error}"#,
+                    drone_name = drone.name,
+                );
+                let formatted_msg = Some(format!(
+                    r#"<h4>🚨 The vinculum reports alarm for drone <font color="cyan">{drone_name}</font>!</h4>
+                <p>The restore drill{archive} failed:<br><pre>{error}</pre></p>"#,
+                    drone_name = drone.name,
+                ));
+
+                (msg, formatted_msg)
+            }
+            Notification::BorgWarnings { drone, warnings } => {
+                let mut listed = warnings
+                    .iter()
+                    .take(MAX_NOTIFIED_WARNINGS)
+                    .map(|x| x.message.clone())
+                    .collect::<Vec<_>>();
+                if warnings.len() > MAX_NOTIFIED_WARNINGS {
+                    listed.push(format!(
+                        "... and {ct} more",
+                        ct = warnings.len() - MAX_NOTIFIED_WARNINGS
+                    ));
+                }
+                let listed = listed.join("\n");
+
+                let msg = format!(
+                    r#"🔔 Borg reported {ct} warnings for drone {drone_name}
+                
+                The archive was created, but borg logged:
+                {listed}"#,
+                    ct = warnings.len(),
+                    drone_name = drone.name,
+                );
+                let formatted_msg = Some(format!(
+                    r#"<h4>🔔 Borg reported {ct} warnings for drone <font color="cyan">{drone_name}</font></h4>
+                <p>The archive was created, but borg logged:<br><pre>{listed}</pre></p>"#,
+                    ct = warnings.len(),
+                    drone_name = drone.name,
+                ));
+
+                (msg, formatted_msg)
+            }
+            Notification::HostnameChanged {
+                drone,
+                previous,
+                current,
+            } => {
+                let msg = format!(
+                    r#"🚨 The vinculum reports alarm for drone {drone_name}!
+                
+                The hostname changed from {previous} to {current}.
+                If this is unexpected, the token of the drone may have leaked."#,
+                    drone_name = drone.name,
+                );
+                let formatted_msg = Some(format!(
+                    r#"<h4>🚨 The vinculum reports alarm for drone <font color="cyan">{drone_name}</font>!</h4>
+                <p>The hostname changed from <code>{previous}</code> to <code>{current}</code>.<br>
+                If this is unexpected, the token of the drone may have leaked.</p>"#,
+                    drone_name = drone.name,
+                ));
+
+                (msg, formatted_msg)
+            }
+            Notification::Warning { drone, message } => {
+                let msg = format!(
+                    r#"⚠️ The vinculum reports a warning for drone {drone_name}!
+                
+                {message}"#,
+                    drone_name = drone.name,
+                );
+                let formatted_msg = Some(format!(
+                    r#"<h4>⚠️ The vinculum reports a warning for drone <font color="cyan">{drone_name}</font>!</h4>
+                <p>{message}</p>"#,
+                    drone_name = drone.name,
+                    message = message.replace('\n', "<br>"),
+                ));
+
+                (msg, formatted_msg)
+            }
+        }
+    }
+}
+
+/// The counters of the matrix notifier
+#[derive(Default)]
+pub struct MatrixNotifierStats {
+    /// The number of successfully sent notifications
+    pub sent: AtomicU64,
+    /// The number of notifications that could not be sent
+    pub failed: AtomicU64,
+}
+
 /// Channel to the matrix notifier
-pub type MatrixNotifierChan = Sender<(Drone, ErrorReport)>;
+#[derive(Clone)]
+pub struct MatrixNotifierChan {
+    tx: Sender<Notification>,
+    stats: Arc<MatrixNotifierStats>,
+}
+
+impl MatrixNotifierChan {
+    /// Queue a notification
+    pub async fn send(&self, notification: Notification) -> Result<(), SendError<Notification>> {
+        self.tx.send(notification).await.map_err(|err| {
+            self.stats.failed.fetch_add(1, Ordering::Relaxed);
+            err
+        })
+    }
+
+    /// The number of notifications that are waiting to be sent
+    pub fn queue_depth(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
+
+    /// The counters of the notifier
+    pub fn stats(&self) -> &MatrixNotifierStats {
+        &self.stats
+    }
+}
 
 async fn perform_login(
     matrix: &mut MatrixApi,
@@ -29,7 +282,9 @@ pub(crate) async fn start_matrix_notifier(
     config: &Config,
     mut matrix: MatrixApi,
 ) -> Result<MatrixNotifierChan, String> {
-    let (tx, mut rx) = mpsc::channel::<(Drone, ErrorReport)>(16);
+    let (tx, mut rx) = mpsc::channel::<Notification>(16);
+    let stats = Arc::new(MatrixNotifierStats::default());
+    let notifier_stats = stats.clone();
 
     let channel = config.matrix.channel.clone();
     let username = config.matrix.username.clone();
@@ -39,49 +294,12 @@ pub(crate) async fn start_matrix_notifier(
     perform_login(&mut matrix, &username, &password, &channel).await?;
 
     tokio::spawn(async move {
-        while let Some((drone, report)) = rx.recv().await {
-            let msg = format!(
-                r#"🚨 The vinculum reports alarm for drone {drone_name}!
-                
-                {drone_name} failed in {state}
-                
-                {custom}{stderr}{stdout}"#,
-                drone_name = drone.name.clone(),
-                state = report.state,
-                custom = report
-                    .custom
-                    .as_ref()
-                    .map_or("".to_string(), |x| format!("Custom error:\n{x}\n\n")),
-                stderr = report
-                    .stderr
-                    .as_ref()
-                    .map_or("".to_string(), |x| format!("Stderr:\n{x}\n\n")),
-                stdout = report
-                    .stdout
-                    .as_ref()
-                    .map_or("".to_string(), |x| format!("Stdout:\n{x}")),
-            );
-            let formatted_msg = Some(format!(
-                r#"<h4>🚨 The vinculum reports alarm for drone <font color="cyan">{drone_name}</font>!</h4>
-                <p><font color="cyan">{drone_name}</font> failed in {state}</p>
-                {custom}
-                {stderr}
-                {stdout}
-            "#,
-                drone_name = drone.name.clone(),
-                state = report.state,
-                custom = report.custom.as_ref().map_or("".to_string(), |x| format!(
-                    "<p>Custom error:<br><code>{x}</code></p>"
-                )),
-                stderr = report.stderr.as_ref().map_or("".to_string(), |x| format!(
-                    "<p>Stderr:<br><pre>{x}</pre></p>"
-                )),
-                stdout = report.stdout.as_ref().map_or("".to_string(), |x| format!(
-                    "<p>Stdout:<br><pre>{x}</pre></p>"
-                )),
-            ));
+        while let Some(notification) = rx.recv().await {
+            let (msg, formatted_msg) = notification.format();
 
             if let Err(err) = matrix.send_message(msg, formatted_msg, &channel).await {
+                notifier_stats.failed.fetch_add(1, Ordering::Relaxed);
+
                 match err {
                     MatrixError::LoginFailed => {
                         if let Err(err) =
@@ -92,9 +310,11 @@ pub(crate) async fn start_matrix_notifier(
                     }
                     _ => warn!("{err}"),
                 }
+            } else {
+                notifier_stats.sent.fetch_add(1, Ordering::Relaxed);
             }
         }
     });
 
-    Ok(tx)
+    Ok(MatrixNotifierChan { tx, stats })
 }
@@ -21,6 +21,22 @@ pub struct BorgConfig {
     pub ssh_key_path: String,
     /// The path where the remote borg is found
     pub borg_remote_path: Option<String>,
+    /// The interval in hours the information about the repositories is collected in
+    #[serde(default = "default_info_interval_hours")]
+    pub info_interval_hours: u32,
+    /// The number of minutes the archives of a repository and their contents are cached
+    ///
+    /// Set to 0 to disable the cache.
+    #[serde(default = "default_archive_cache_minutes")]
+    pub archive_cache_minutes: u32,
+}
+
+fn default_info_interval_hours() -> u32 {
+    6
+}
+
+fn default_archive_cache_minutes() -> u32 {
+    15
 }
 
 /// The configuration of the connection to a matrix server
@@ -69,6 +85,138 @@ pub struct DBConfig {
     pub password: String,
 }
 
+/// Configuration regarding the prometheus metrics
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct MetricsConfig {
+    /// The token that must be provided as bearer token to scrape the metrics
+    pub scrape_token: String,
+    /// The number of hours after the last successful report an active drone is considered overdue
+    #[serde(default = "default_overdue_after_hours")]
+    pub overdue_after_hours: u32,
+}
+
+fn default_overdue_after_hours() -> u32 {
+    25
+}
+
+/// Configuration regarding the anomaly detection of reported stats
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct AnomalyConfig {
+    /// The number of previous stats the baseline is calculated from
+    pub baseline_size: u32,
+    /// The minimum number of previous stats that are required to detect anomalies
+    pub min_baseline_size: u32,
+    /// The relative deviation from the baseline at which a value is considered an anomaly.
+    ///
+    /// `0.5` means that values below 50% or above 150% of the baseline are reported.
+    pub threshold: f64,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            baseline_size: 10,
+            min_baseline_size: 3,
+            threshold: 0.5,
+        }
+    }
+}
+
+/// Configuration regarding the forecast of the size of repositories
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ForecastConfig {
+    /// The number of days before the estimated date a capacity limit is reached
+    /// at which an alert is sent
+    pub alert_days: u32,
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        Self { alert_days: 14 }
+    }
+}
+
+/// Configuration regarding the clocks of the drones
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClockSkewConfig {
+    /// The skew in seconds between the clock of a drone and the clock of the vinculum
+    /// at which an alert is sent
+    pub tolerance_seconds: u32,
+}
+
+impl Default for ClockSkewConfig {
+    fn default() -> Self {
+        Self {
+            tolerance_seconds: 300,
+        }
+    }
+}
+
+/// Configuration regarding the retention of the stats of drones
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct RetentionConfig {
+    /// The number of days raw stats are kept.
+    ///
+    /// Older stats are rolled into daily aggregates.
+    pub raw_days: u32,
+    /// The number of days daily aggregates are kept.
+    ///
+    /// Older daily aggregates are rolled into weekly aggregates.
+    /// Must not be less than `RawDays`.
+    pub daily_days: u32,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            raw_days: 30,
+            daily_days: 365,
+        }
+    }
+}
+
+/// Configuration regarding the restore of files from the repositories
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct RestoreConfig {
+    /// The usernames of the accounts that may access the contents of archives
+    ///
+    /// If empty, no account can access the contents of archives.
+    pub allowed_accounts: Vec<String>,
+}
+
+/// Configuration regarding the restore drills
+///
+/// A drill restores some random files of a random archive of every active drone
+/// to verify that the backups can be restored.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct DrillConfig {
+    /// The directory the files are restored to
+    ///
+    /// The restored files are removed after each drill.
+    pub scratch_dir: String,
+    /// The interval in hours the drills are executed in
+    #[serde(default = "default_drill_interval_hours")]
+    pub interval_hours: u32,
+    /// The number of files that are restored per drill
+    #[serde(default = "default_drill_files")]
+    pub files: u32,
+}
+
+fn default_drill_interval_hours() -> u32 {
+    7 * 24
+}
+
+fn default_drill_files() -> u32 {
+    5
+}
+
 /// The configuration file of borg-vinculum
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "PascalCase")]
@@ -83,6 +231,29 @@ pub struct Config {
     pub matrix: MatrixConfig,
     /// The borg related configuration
     pub borg: BorgConfig,
+    /// The metrics configuration
+    ///
+    /// If not set, the metrics endpoint is disabled.
+    pub metrics: Option<MetricsConfig>,
+    /// The anomaly detection configuration
+    #[serde(default)]
+    pub anomaly: AnomalyConfig,
+    /// The forecast configuration
+    #[serde(default)]
+    pub forecast: ForecastConfig,
+    /// The clock skew configuration
+    #[serde(default)]
+    pub clock_skew: ClockSkewConfig,
+    /// The retention configuration of drone stats
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    /// The restore configuration
+    #[serde(default)]
+    pub restore: RestoreConfig,
+    /// The restore drill configuration
+    ///
+    /// If not set, no restore drills are executed.
+    pub drill: Option<DrillConfig>,
     /// The private key
     #[serde(skip)]
     pub private_key: Option<PrivateKey>,
@@ -97,6 +268,40 @@ impl TryFrom<&Path> for Config {
         let mut conf: Config = toml::from_str(&config_str)
             .map_err(|e| format!("Error deserializing config from: {e}"))?;
 
+        if conf.borg.info_interval_hours == 0 {
+            return Err("Borg.InfoIntervalHours must be greater than 0".to_string());
+        }
+
+        if conf.anomaly.min_baseline_size == 0
+            || conf.anomaly.baseline_size < conf.anomaly.min_baseline_size
+        {
+            return Err(
+                "Anomaly.MinBaselineSize must be between 1 and Anomaly.BaselineSize".to_string(),
+            );
+        }
+        if conf.anomaly.threshold <= 0.0 {
+            return Err("Anomaly.Threshold must be greater than 0".to_string());
+        }
+
+        if let Some(metrics) = &conf.metrics {
+            if metrics.scrape_token.is_empty() {
+                return Err("Metrics.ScrapeToken must not be empty".to_string());
+            }
+        }
+
+        if conf.retention.daily_days < conf.retention.raw_days {
+            return Err("Retention.DailyDays must not be less than Retention.RawDays".to_string());
+        }
+
+        if let Some(drill) = &conf.drill {
+            if drill.interval_hours == 0 {
+                return Err("Drill.IntervalHours must be greater than 0".to_string());
+            }
+            if drill.files == 0 {
+                return Err("Drill.Files must be greater than 0".to_string());
+            }
+        }
+
         let pk = retrieve_ssh_key(&conf)?;
         conf.private_key = Some(pk);
 
@@ -1,45 +1,100 @@
 use actix_web::web::{Data, Json};
-use actix_web::{post, HttpRequest, HttpResponse};
-use chrono::Utc;
-use common::{ErrorReport, StatReport};
-use log::{debug, warn};
+use actix_web::{get, post, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use common::{
+    ErrorReport, GetPrunePolicyResponse, HostInventory, PruneStats, RestoreReport, StatReport,
+};
+use log::{debug, error, info, warn};
 use rorm::executor::Executor;
 use rorm::fields::ForeignModelByField;
-use rorm::{insert, query, update, Database, Model};
+use rorm::{and, insert, query, update, Database, Model};
 use uuid::Uuid;
 
-use crate::chan::MatrixNotifierChan;
-use crate::handler::{ApiError, ApiResult};
-use crate::models::{Drone, DroneStatsInsert};
+use crate::chan::{MatrixNotifierChan, Notification};
+use crate::config::Config;
+use crate::handler::{bearer_token, ApiError, ApiResult};
+use crate::models::{
+    Drone, DronePruneInsert, DronePrunePolicy, DroneRestoreInsert, DroneRule,
+    DroneRuleViolationInsert, DroneStats, DroneStatsBaseline, DroneStatsInsert, DroneWarningInsert,
+};
+use crate::modules::anomaly::detect_anomalies;
+use crate::modules::archives::ArchiveCache;
+use crate::modules::inventory::{update_inventory, HostnameChange};
+use crate::modules::rules::check_rule;
+
+/// The maximum length of the recorded paths, target and error of a restore
+const MAX_RESTORE_FIELD_LENGTH: usize = 4096;
+
+/// The maximum length of the stored changed paths of a report as json
+const MAX_CHANGED_PATHS_LENGTH: usize = 262144;
+
+/// The maximum number of borg warnings that are stored per report
+const MAX_STORED_WARNINGS: usize = 1000;
+/// The maximum length of the stored message and path of a borg warning
+const MAX_WARNING_FIELD_LENGTH: usize = 4096;
 
 async fn check_auth<'a>(tx: impl Executor<'a>, raw_req: &HttpRequest) -> ApiResult<Drone> {
     // Retrieve drone and check for authentication
-    if let Some(auth_header) = raw_req.headers().get("Authorization") {
-        let auth_value = auth_header.to_str().map_err(|e| {
-            debug!("Invalid characters in header: {e}");
-            ApiError::Unauthenticated
-        })?;
-
-        let h: Vec<&str> = auth_value.split(' ').collect();
-        if h.len() != 2 {
-            return Err(ApiError::Unauthenticated);
-        }
+    let token = bearer_token(raw_req)?;
+
+    let drone = query!(tx, Drone)
+        .condition(and!(
+            Drone::F.token.equals(token),
+            Drone::F.archived_at.is_null()
+        ))
+        .optional()
+        .await?
+        .ok_or(ApiError::Unauthenticated)?;
 
-        if *h.first().unwrap() != "Bearer" {
-            return Err(ApiError::Unauthenticated);
+    Ok(drone)
+}
+
+/// Store the inventory of the host of a drone and alert if its hostname changed
+///
+/// Errors are only logged, as the report itself is already stored at this point.
+/// Failing the request would make the drone send the report again.
+async fn report_inventory(
+    db: &Database,
+    matrix: &MatrixNotifierChan,
+    drone: &Uuid,
+    host: Option<&HostInventory>,
+) {
+    let Some(host) = host else {
+        return;
+    };
+
+    let change = match update_inventory(db, drone, host).await {
+        Ok(change) => change,
+        Err(err) => {
+            error!("Error while storing the inventory of drone {drone}: {err}");
+            return;
         }
+    };
 
-        let token = *h.get(1).unwrap();
+    if let Some(HostnameChange {
+        drone,
+        previous,
+        current,
+    }) = change
+    {
+        warn!(
+            "Drone {name} changed its hostname from {previous} to {current}",
+            name = drone.name
+        );
 
-        let drone = query!(tx, Drone)
-            .condition(Drone::F.token.equals(token))
-            .optional()
-            .await?
-            .ok_or(ApiError::Unauthenticated)?;
+        // Inactive drones are not notified about
+        if !drone.active {
+            return;
+        }
 
-        Ok(drone)
-    } else {
-        Err(ApiError::Unauthenticated)
+        let notification = Notification::HostnameChanged {
+            drone,
+            previous,
+            current,
+        };
+        if let Err(err) = matrix.send(notification).await {
+            warn!("Error while sending to matrix notifier chan: {err}");
+        }
     }
 }
 
@@ -59,11 +114,14 @@ pub async fn stats(
     req: Json<StatReport>,
     raw_req: HttpRequest,
     db: Data<Database>,
+    config: Data<Config>,
+    matrix: Data<MatrixNotifierChan>,
+    archive_cache: Data<ArchiveCache>,
 ) -> ApiResult<HttpResponse> {
     let mut tx = db.start_transaction().await?;
 
     // Retrieve drone and check for authentication
-    let drone = check_auth(&mut tx, &raw_req).await?;
+    let mut drone = check_auth(&mut tx, &raw_req).await?;
 
     let mut complete_duration = req.create_stats.duration;
     if let Some(pre) = req.pre_hook_stats {
@@ -73,30 +131,252 @@ pub async fn stats(
         complete_duration += post.duration
     }
 
+    let changed_files = req.changed_files.as_ref();
+    let mut changed_paths = changed_files
+        .and_then(|x| x.paths.as_ref())
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|_| ApiError::InternalServerError)?;
+    let mut changed_paths_truncated = changed_files.map_or(false, |x| x.paths_truncated);
+    if changed_paths
+        .as_ref()
+        .map_or(false, |x| x.chars().count() > MAX_CHANGED_PATHS_LENGTH)
+    {
+        warn!(
+            "The changed paths reported by drone {name} are too long to store",
+            name = drone.name
+        );
+        changed_paths = None;
+        changed_paths_truncated = true;
+    }
+
+    // The clock of the drone may deviate from the clock of the vinculum,
+    // the reported points in time are corrected by the skew when ordering stats
+    let now = Utc::now();
+    let clock_skew = req.sent_at.map(|x| (x - now).num_seconds());
+    let collected_at = req
+        .end
+        .map(|x| x - Duration::seconds(clock_skew.unwrap_or(0)))
+        .map_or(now, |x| x.min(now));
+    let skew_exceeded =
+        clock_skew.filter(|x| x.unsigned_abs() > config.clock_skew.tolerance_seconds as u64);
+    if let Some(skew) = skew_exceeded {
+        warn!(
+            "The clock of drone {name} is skewed by {skew} seconds",
+            name = drone.name
+        );
+    }
+
+    let repository = req.create_stats.repository;
+    let mut stats = DroneStatsInsert {
+        uuid: Uuid::new_v4(),
+        drone: ForeignModelByField::Key(drone.uuid),
+        pre_hook_duration: req.pre_hook_stats.map(|x| x.duration as i64),
+        post_hook_duration: req.post_hook_stats.map(|x| x.duration as i64),
+        create_duration: req.create_stats.duration as i64,
+        complete_duration: complete_duration as i64,
+        original_size: req.create_stats.original_size as i64,
+        compressed_size: req.create_stats.compressed_size as i64,
+        deduplicated_size: req.create_stats.deduplicated_size as i64,
+        nfiles: req.create_stats.nfiles as i64,
+        anomaly: false,
+        warnings: req.warnings.len() as i64,
+        files_added: changed_files.map(|x| x.added as i64),
+        files_modified: changed_files.map(|x| x.modified as i64),
+        files_unchanged: changed_files.map(|x| x.unchanged as i64),
+        changed_paths,
+        changed_paths_truncated,
+        archive_name: req.create_stats.archive_name.clone(),
+        archive_id: req.create_stats.archive_id.clone(),
+        archive_start: req.create_stats.archive_start,
+        archive_end: req.create_stats.archive_end,
+        repository_total_chunks: repository.map(|x| x.total_chunks as i64),
+        repository_total_csize: repository.map(|x| x.total_csize as i64),
+        repository_total_size: repository.map(|x| x.total_size as i64),
+        repository_total_unique_chunks: repository.map(|x| x.total_unique_chunks as i64),
+        repository_unique_csize: repository.map(|x| x.unique_csize as i64),
+        repository_unique_size: repository.map(|x| x.unique_size as i64),
+        started_at: req.start.map(|x| x.naive_utc()),
+        finished_at: req.end.map(|x| x.naive_utc()),
+        pre_hook_started_at: req
+            .pre_hook_stats
+            .and_then(|x| x.start)
+            .map(|x| x.naive_utc()),
+        pre_hook_finished_at: req
+            .pre_hook_stats
+            .and_then(|x| x.end)
+            .map(|x| x.naive_utc()),
+        post_hook_started_at: req
+            .post_hook_stats
+            .and_then(|x| x.start)
+            .map(|x| x.naive_utc()),
+        post_hook_finished_at: req
+            .post_hook_stats
+            .and_then(|x| x.end)
+            .map(|x| x.naive_utc()),
+        clock_skew,
+        collected_at: Some(collected_at.naive_utc()),
+    };
+
+    let baseline = query!(&mut tx, DroneStatsBaseline)
+        .condition(DroneStats::F.drone.equals(drone.uuid.as_ref()))
+        .order_desc(DroneStats::F.collected_at)
+        .limit(config.anomaly.baseline_size as u64)
+        .all()
+        .await?;
+
+    let reported = DroneStatsBaseline {
+        original_size: stats.original_size,
+        nfiles: stats.nfiles,
+        deduplicated_size: stats.deduplicated_size,
+        create_duration: stats.create_duration,
+    };
+    let anomalies = detect_anomalies(&config.anomaly, &baseline, &reported);
+    stats.anomaly = !anomalies.is_empty();
+
     insert!(&mut tx, DroneStatsInsert)
         .return_nothing()
-        .single(&DroneStatsInsert {
+        .single(&stats)
+        .await?;
+
+    if req.warnings.len() > MAX_STORED_WARNINGS {
+        warn!(
+            "Drone {name} reported {ct} warnings, storing only the first {MAX_STORED_WARNINGS}",
+            name = drone.name,
+            ct = req.warnings.len(),
+        );
+    }
+    let truncate = |x: &str| x.chars().take(MAX_WARNING_FIELD_LENGTH).collect::<String>();
+    let warnings: Vec<_> = req
+        .warnings
+        .iter()
+        .take(MAX_STORED_WARNINGS)
+        .map(|x| DroneWarningInsert {
             uuid: Uuid::new_v4(),
             drone: ForeignModelByField::Key(drone.uuid),
-            pre_hook_duration: req.pre_hook_stats.map(|x| x.duration as i64),
-            post_hook_duration: req.pre_hook_stats.map(|x| x.duration as i64),
-            create_duration: req.create_stats.duration as i64,
-            complete_duration: complete_duration as i64,
-            original_size: req.create_stats.original_size as i64,
-            compressed_size: req.create_stats.compressed_size as i64,
-            deduplicated_size: req.create_stats.deduplicated_size as i64,
-            nfiles: req.create_stats.nfiles as i64,
+            stats: Some(ForeignModelByField::Key(stats.uuid)),
+            message: truncate(&x.message),
+            path: x.path.as_deref().map(truncate),
         })
+        .collect();
+    if !warnings.is_empty() {
+        insert!(&mut tx, DroneWarningInsert)
+            .return_nothing()
+            .bulk(&warnings)
+            .await?;
+    }
+
+    let rules = query!(&mut tx, DroneRule)
+        .condition(and!(
+            DroneRule::F.drone.equals(drone.uuid.as_ref()),
+            DroneRule::F.deleted_at.is_null()
+        ))
+        .all()
         .await?;
 
+    let mut violations = vec![];
+    for rule in rules {
+        if let Some(value) = check_rule(&rule, &stats) {
+            insert!(&mut tx, DroneRuleViolationInsert)
+                .return_nothing()
+                .single(&DroneRuleViolationInsert {
+                    uuid: Uuid::new_v4(),
+                    rule: ForeignModelByField::Key(rule.uuid),
+                    stats: Some(ForeignModelByField::Key(stats.uuid)),
+                    value,
+                })
+                .await?;
+
+            violations.push(format!(
+                "{field} {operator} {configured} is violated by {value}",
+                field = rule.field,
+                operator = rule.operator,
+                configured = rule.value,
+            ));
+        }
+    }
+
     update!(&mut tx, Drone)
         .condition(Drone::F.uuid.equals(drone.uuid.as_ref()))
         .set(Drone::F.last_activity, Utc::now().naive_utc())
         .exec()
         .await?;
 
+    // A successful report activates the drone, unless it was deactivated explicitly
+    if !drone.active && !drone.deactivated {
+        info!(
+            "Activating drone {name} on its successful report",
+            name = drone.name
+        );
+
+        update!(&mut tx, Drone)
+            .condition(Drone::F.uuid.equals(drone.uuid.as_ref()))
+            .set(Drone::F.active, true)
+            .exec()
+            .await?;
+        drone.active = true;
+    }
+
     tx.commit().await?;
 
+    // The drone has created a new archive
+    archive_cache.invalidate(&drone.uuid);
+
+    report_inventory(&db, &matrix, &drone.uuid, req.host.as_ref()).await;
+
+    // Borg warnings are notified on their own, as the archive was created nonetheless
+    if !req.warnings.is_empty() && drone.active {
+        let notified = query!(db.as_ref(), Drone)
+            .condition(Drone::F.uuid.equals(drone.uuid.as_ref()))
+            .one()
+            .await;
+        match notified {
+            Ok(notified) => {
+                let notification = Notification::BorgWarnings {
+                    drone: notified,
+                    warnings: req.into_inner().warnings,
+                };
+
+                if let Err(err) = matrix.send(notification).await {
+                    warn!("Error while sending to matrix notifier chan: {err}");
+                }
+            }
+            Err(err) => error!("Error while retrieving drone for notification: {err}"),
+        }
+    }
+
+    let mut warnings = vec![];
+    if !violations.is_empty() {
+        warnings.push(format!(
+            "The reported stats violate the rules of the drone:\n{violations}",
+            violations = violations.join("\n"),
+        ));
+    }
+    if let Some(skew) = skew_exceeded {
+        warnings.push(format!(
+            "The clock of the drone deviates by {skew} seconds from the clock of the vinculum"
+        ));
+    }
+    if !anomalies.is_empty() {
+        warnings.push(format!(
+            "The reported stats deviate from the baseline of the last {ct} reports:\n{anomalies}",
+            ct = baseline.len(),
+            anomalies = anomalies
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ));
+    }
+
+    if !warnings.is_empty() && drone.active {
+        let message = warnings.join("\n\n");
+
+        if let Err(err) = matrix.send(Notification::Warning { drone, message }).await {
+            warn!("Error while sending to matrix notifier chan: {err}");
+        }
+    }
+
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -122,9 +402,165 @@ pub async fn error(
     let drone = check_auth(db.as_ref(), &raw_req).await?;
     let report = req.into_inner();
 
-    if let Err(err) = matrix.send((drone, report)).await {
+    update!(db.as_ref(), Drone)
+        .condition(Drone::F.uuid.equals(drone.uuid.as_ref()))
+        .set(Drone::F.last_error, Some(Utc::now().naive_utc()))
+        .exec()
+        .await?;
+
+    report_inventory(&db, &matrix, &drone.uuid, report.host.as_ref()).await;
+
+    if !drone.active {
+        debug!(
+            "Suppressing error of inactive drone {name}: {report:?}",
+            name = drone.name
+        );
+        return Ok(HttpResponse::Ok().finish());
+    }
+
+    if let Err(err) = matrix.send(Notification::Error { drone, report }).await {
         warn!("Error while sending to matrix notifier chan: {err}");
     }
 
     Ok(HttpResponse::Ok().finish())
 }
+
+/// Report a restore of an archive to the vinculum
+#[utoipa::path(
+    context_path = "/api/drone/v1",
+    responses(
+        (status = 200, description = "Restore reported"),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    request_body = RestoreReport,
+    security(("token" = [])),
+)]
+#[post("/restore")]
+pub async fn restore(
+    req: Json<RestoreReport>,
+    raw_req: HttpRequest,
+    db: Data<Database>,
+    matrix: Data<MatrixNotifierChan>,
+) -> ApiResult<HttpResponse> {
+    let mut tx = db.start_transaction().await?;
+
+    // Retrieve drone and check for authentication
+    let drone = check_auth(&mut tx, &raw_req).await?;
+    let report = req.into_inner();
+
+    if report.archive.is_empty() || report.archive.len() > 255 {
+        return Err(ApiError::InvalidArchive);
+    }
+
+    info!(
+        "Drone {name} restored archive {archive} into {target}, success: {success}",
+        name = drone.name,
+        archive = report.archive,
+        target = report.target,
+        success = report.success,
+    );
+
+    let truncate = |x: &str| x.chars().take(MAX_RESTORE_FIELD_LENGTH).collect::<String>();
+
+    insert!(&mut tx, DroneRestoreInsert)
+        .return_nothing()
+        .single(&DroneRestoreInsert {
+            uuid: Uuid::new_v4(),
+            drone: ForeignModelByField::Key(drone.uuid),
+            paths: truncate(&report.paths.join("\n")),
+            target: truncate(&report.target),
+            error: report.error.as_deref().map(truncate),
+            archive: report.archive,
+            success: report.success,
+            duration: report.duration as i64,
+        })
+        .await?;
+
+    tx.commit().await?;
+
+    report_inventory(&db, &matrix, &drone.uuid, report.host.as_ref()).await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Retrieve the retention policy of the drone
+///
+/// If `policy` is not set, there is no retention policy configured for the drone.
+#[utoipa::path(
+    context_path = "/api/drone/v1",
+    responses(
+        (status = 200, description = "Retrieved the retention policy", body = GetPrunePolicyResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    security(("token" = [])),
+)]
+#[get("/prune-policy")]
+pub async fn prune_policy(
+    raw_req: HttpRequest,
+    db: Data<Database>,
+) -> ApiResult<Json<GetPrunePolicyResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    // Retrieve drone and check for authentication
+    let drone = check_auth(&mut tx, &raw_req).await?;
+
+    let policy = query!(&mut tx, DronePrunePolicy)
+        .condition(DronePrunePolicy::F.drone.equals(drone.uuid.as_ref()))
+        .optional()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetPrunePolicyResponse {
+        policy: policy.map(Into::into),
+    }))
+}
+
+/// Report the stats of a prune to the vinculum
+#[utoipa::path(
+    context_path = "/api/drone/v1",
+    responses(
+        (status = 200, description = "Prune reported"),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    request_body = PruneStats,
+    security(("token" = [])),
+)]
+#[post("/prune")]
+pub async fn prune(
+    req: Json<PruneStats>,
+    raw_req: HttpRequest,
+    db: Data<Database>,
+    matrix: Data<MatrixNotifierChan>,
+    archive_cache: Data<ArchiveCache>,
+) -> ApiResult<HttpResponse> {
+    let mut tx = db.start_transaction().await?;
+
+    // Retrieve drone and check for authentication
+    let drone = check_auth(&mut tx, &raw_req).await?;
+
+    insert!(&mut tx, DronePruneInsert)
+        .return_nothing()
+        .single(&DronePruneInsert {
+            uuid: Uuid::new_v4(),
+            drone: ForeignModelByField::Key(drone.uuid),
+            pruned: req.pruned as i64,
+            kept: req.kept as i64,
+            duration: req.duration as i64,
+        })
+        .await?;
+
+    tx.commit().await?;
+
+    // Archives of the drone were removed
+    if req.pruned > 0 {
+        archive_cache.invalidate(&drone.uuid);
+    }
+
+    report_inventory(&db, &matrix, &drone.uuid, req.host.as_ref()).await;
+
+    Ok(HttpResponse::Ok().finish())
+}
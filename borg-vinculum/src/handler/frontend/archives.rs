@@ -0,0 +1,119 @@
+use actix_web::get;
+use actix_web::web::{Data, Json, Path, Query};
+use borgbackup::common::CommonOptions;
+use chrono::{DateTime, Utc};
+use rorm::{query, Database, Model};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::handler::{ApiError, ApiResult, PathUuid};
+use crate::models::Drone;
+use crate::modules::archives::ArchiveCache;
+use crate::modules::borg::info_archives;
+
+/// The query parameters to retrieve the archives of a drone
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetArchivesQuery {
+    /// Bypass the cache and retrieve the archives from the repository
+    #[serde(default)]
+    refresh: bool,
+}
+
+/// An archive in the repository of a drone
+#[derive(Serialize, ToSchema)]
+pub struct Archive {
+    /// The name of the archive
+    name: String,
+    /// The hexadecimal id of the archive
+    id: String,
+    /// The point in time the creation of the archive started
+    start: DateTime<Utc>,
+    /// The point in time the creation of the archive ended
+    end: DateTime<Utc>,
+    /// The duration of the creation in seconds
+    duration: f64,
+    /// The hostname of the creating host
+    hostname: String,
+    /// The name of the creating user
+    username: String,
+    /// The comment of the archive
+    comment: String,
+    /// Original file size in bytes
+    original_size: u64,
+    /// Compressed file size in bytes
+    compressed_size: u64,
+    /// Deduplicated file size in bytes, against the current repository
+    deduplicated_size: u64,
+    /// Number of archived files
+    nfiles: u64,
+}
+
+/// The archives of a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetArchivesResponse {
+    archives: Vec<Archive>,
+    /// The point in time the archives were retrieved from the repository
+    retrieved_at: DateTime<Utc>,
+}
+
+/// Retrieve the archives in the repository of a drone
+///
+/// The archives are retrieved using `borg info` and cached for some minutes.
+/// The cache is invalidated when the drone reports new stats.
+/// Use `refresh` to bypass the cache.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the archives of the drone", body = GetArchivesResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid, GetArchivesQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/archives")]
+pub async fn get_archives(
+    path: Path<PathUuid>,
+    query: Query<GetArchivesQuery>,
+    db: Data<Database>,
+    common_options: Data<CommonOptions>,
+    cache: Data<ArchiveCache>,
+) -> ApiResult<Json<GetArchivesResponse>> {
+    let drone = query!(db.as_ref(), Drone)
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?;
+
+    let cached = match cache.get(&drone.uuid) {
+        Some(cached) if !query.refresh => cached,
+        _ => {
+            let info = info_archives(&common_options, &drone.repository, &drone.passphrase).await?;
+            cache.insert(drone.uuid, info.archives)
+        }
+    };
+
+    Ok(Json(GetArchivesResponse {
+        archives: cached
+            .archives
+            .iter()
+            .map(|x| Archive {
+                name: x.name.clone(),
+                id: x.id.clone(),
+                start: DateTime::from_utc(x.start, Utc),
+                end: DateTime::from_utc(x.end, Utc),
+                duration: x.duration,
+                hostname: x.hostname.clone(),
+                username: x.username.clone(),
+                comment: x.comment.clone(),
+                original_size: x.stats.original_size,
+                compressed_size: x.stats.compressed_size,
+                deduplicated_size: x.stats.deduplicated_size,
+                nfiles: x.stats.nfiles,
+            })
+            .collect(),
+        retrieved_at: DateTime::from_utc(cached.retrieved_at, Utc),
+    }))
+}
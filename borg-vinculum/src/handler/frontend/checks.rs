@@ -0,0 +1,276 @@
+use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{delete, get, put, HttpResponse};
+use chrono::{DateTime, Utc};
+use rorm::conditions::{BoxedCondition, Condition, DynamicCollection};
+use rorm::fields::ForeignModelByField;
+use rorm::{insert, query, Database, Model};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::handler::{ApiError, ApiResult, PathUuid};
+use crate::models::{CheckMode, Drone, DroneCheck, DroneCheckPolicy, DroneCheckPolicyInsert};
+
+/// The maximum interval of checks in hours, which is roughly 10 years
+const MAX_CHECK_INTERVAL_HOURS: i64 = 10 * 365 * 24;
+
+/// The policy of the integrity checks of the repository of a drone
+///
+/// `interval_hours` must be between 1 and 87600 (roughly 10 years).
+///
+/// `max_duration` limits a check to a partial check of the given number of seconds.
+/// The next check continues where the previous one stopped.
+/// It is only supported by `RepositoryOnly`.
+#[derive(Deserialize, Serialize, ToSchema)]
+pub struct CheckPolicy {
+    mode: CheckMode,
+    #[schema(example = 168)]
+    interval_hours: i64,
+    #[schema(example = 3600)]
+    max_duration: Option<i64>,
+}
+
+/// The check policy of a drone
+///
+/// If `policy` is not set, the repository is not checked.
+#[derive(Serialize, ToSchema)]
+pub struct GetCheckPolicyResponse {
+    policy: Option<CheckPolicy>,
+}
+
+/// Retrieve the check policy of a drone
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the check policy", body = GetCheckPolicyResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/check-policy")]
+pub async fn get_check_policy(
+    path: Path<PathUuid>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetCheckPolicyResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let policy = query!(&mut tx, DroneCheckPolicy)
+        .condition(DroneCheckPolicy::F.drone.equals(drone.as_ref()))
+        .optional()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetCheckPolicyResponse {
+        policy: policy.map(|x| CheckPolicy {
+            mode: x.mode,
+            interval_hours: x.interval_hours,
+            max_duration: x.max_duration,
+        }),
+    }))
+}
+
+/// Set the check policy of a drone
+///
+/// An existing policy is replaced.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Check policy got set"),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    request_body = CheckPolicy,
+    security(("session_cookie" = [])),
+)]
+#[put("/drones/{uuid}/check-policy")]
+pub async fn set_check_policy(
+    path: Path<PathUuid>,
+    req: Json<CheckPolicy>,
+    db: Data<Database>,
+) -> ApiResult<HttpResponse> {
+    if req.interval_hours <= 0 || req.interval_hours > MAX_CHECK_INTERVAL_HOURS {
+        return Err(ApiError::InvalidCheckPolicy);
+    }
+    if let Some(max_duration) = req.max_duration {
+        if max_duration <= 0 || req.mode != CheckMode::RepositoryOnly {
+            return Err(ApiError::InvalidCheckPolicy);
+        }
+    }
+
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    rorm::delete!(&mut tx, DroneCheckPolicy)
+        .condition(DroneCheckPolicy::F.drone.equals(drone.as_ref()))
+        .await?;
+
+    insert!(&mut tx, DroneCheckPolicyInsert)
+        .return_nothing()
+        .single(&DroneCheckPolicyInsert {
+            uuid: Uuid::new_v4(),
+            drone: ForeignModelByField::Key(drone),
+            mode: req.mode,
+            interval_hours: req.interval_hours,
+            max_duration: req.max_duration,
+        })
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Remove the check policy of a drone
+///
+/// The repository is not checked anymore, the results of previous checks are kept.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Check policy got removed"),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    security(("session_cookie" = [])),
+)]
+#[delete("/drones/{uuid}/check-policy")]
+pub async fn delete_check_policy(
+    path: Path<PathUuid>,
+    db: Data<Database>,
+) -> ApiResult<HttpResponse> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    rorm::delete!(&mut tx, DroneCheckPolicy)
+        .condition(DroneCheckPolicy::F.drone.equals(drone.as_ref()))
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// The query parameters to retrieve the checks of a drone
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetChecksQuery {
+    /// Only include checks finished at or after this point in time
+    from: Option<DateTime<Utc>>,
+    /// Only include checks finished before this point in time
+    to: Option<DateTime<Utc>>,
+}
+
+/// The result of an integrity check of the repository
+///
+/// `output` contains the problems borg found, or the error that occurred while executing borg.
+#[derive(Serialize, ToSchema)]
+pub struct RepositoryCheck {
+    uuid: Uuid,
+    mode: CheckMode,
+    /// Whether the check was limited by a maximum duration
+    partial: bool,
+    success: bool,
+    /// The duration in seconds the check took
+    duration: i64,
+    output: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+/// The integrity checks of the repository of a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetChecksResponse {
+    checks: Vec<RepositoryCheck>,
+}
+
+/// Retrieve the results of the integrity checks of the repository of a drone
+///
+/// The checks are ordered by the point in time they finished, starting with the oldest.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the checks of the drone", body = GetChecksResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid, GetChecksQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/checks")]
+pub async fn get_checks(
+    path: Path<PathUuid>,
+    query: Query<GetChecksQuery>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetChecksResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let mut conditions: Vec<BoxedCondition<'_>> =
+        vec![DroneCheck::F.drone.equals(drone.as_ref()).boxed()];
+    if let Some(from) = query.from {
+        conditions.push(
+            DroneCheck::F
+                .created_at
+                .greater_or_equals(from.naive_utc())
+                .boxed(),
+        );
+    }
+    if let Some(to) = query.to {
+        conditions.push(DroneCheck::F.created_at.less(to.naive_utc()).boxed());
+    }
+
+    let checks = query!(&mut tx, DroneCheck)
+        .condition(DynamicCollection::and(conditions))
+        .order_asc(DroneCheck::F.created_at)
+        .all()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetChecksResponse {
+        checks: checks
+            .into_iter()
+            .map(|x| RepositoryCheck {
+                uuid: x.uuid,
+                mode: x.mode,
+                partial: x.partial,
+                success: x.success,
+                duration: x.duration,
+                output: x.output,
+                created_at: DateTime::from_utc(x.created_at, Utc),
+            })
+            .collect(),
+    }))
+}
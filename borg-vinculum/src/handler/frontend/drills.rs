@@ -0,0 +1,227 @@
+use actix_web::get;
+use actix_web::web::{Data, Json, Path, Query};
+use chrono::{DateTime, Utc};
+use rorm::conditions::{BoxedCondition, Condition, DynamicCollection};
+use rorm::{query, Database, Model};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::handler::{ApiError, ApiResult, PathUuid};
+use crate::models::{Drone, DroneRestore, RestoreDrill};
+
+/// The query parameters to retrieve the restore drills of a drone
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetDrillsQuery {
+    /// Only include drills executed at or after this point in time
+    from: Option<DateTime<Utc>>,
+    /// Only include drills executed before this point in time
+    to: Option<DateTime<Utc>>,
+}
+
+/// The result of a restore drill
+#[derive(Serialize, ToSchema)]
+pub struct RestoreDrillResponse {
+    uuid: Uuid,
+    /// The archive the files were restored from, if one could be selected
+    archive: Option<String>,
+    /// Whether the files were restored successfully
+    success: bool,
+    /// The number of restored files
+    files: i64,
+    /// The number of restored bytes
+    bytes_restored: i64,
+    /// The duration in milliseconds the restore took
+    duration_ms: i64,
+    /// The error that occurred during the drill
+    error: Option<String>,
+    /// The point in time the drill was executed
+    created_at: DateTime<Utc>,
+}
+
+/// The restore drills of a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetDrillsResponse {
+    drills: Vec<RestoreDrillResponse>,
+}
+
+/// Retrieve the restore drills of a drone
+///
+/// A drill periodically restores random files of a random archive to verify
+/// that the backups can be restored.
+/// The drills are ordered by the point in time they were executed, starting with the oldest.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the restore drills of the drone", body = GetDrillsResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid, GetDrillsQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/drills")]
+pub async fn get_drills(
+    path: Path<PathUuid>,
+    query: Query<GetDrillsQuery>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetDrillsResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let mut conditions: Vec<BoxedCondition<'_>> =
+        vec![RestoreDrill::F.drone.equals(drone.as_ref()).boxed()];
+    if let Some(from) = query.from {
+        conditions.push(
+            RestoreDrill::F
+                .created_at
+                .greater_or_equals(from.naive_utc())
+                .boxed(),
+        );
+    }
+    if let Some(to) = query.to {
+        conditions.push(RestoreDrill::F.created_at.less(to.naive_utc()).boxed());
+    }
+
+    let drills = query!(&mut tx, RestoreDrill)
+        .condition(DynamicCollection::and(conditions))
+        .order_asc(RestoreDrill::F.created_at)
+        .all()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetDrillsResponse {
+        drills: drills
+            .into_iter()
+            .map(|x| RestoreDrillResponse {
+                uuid: x.uuid,
+                archive: x.archive,
+                success: x.success,
+                files: x.files,
+                bytes_restored: x.bytes_restored,
+                duration_ms: x.duration_ms,
+                error: x.error,
+                created_at: DateTime::from_utc(x.created_at, Utc),
+            })
+            .collect(),
+    }))
+}
+
+/// The query parameters to retrieve the restores reported by a drone
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetDroneRestoresQuery {
+    /// Only include restores reported at or after this point in time
+    from: Option<DateTime<Utc>>,
+    /// Only include restores reported before this point in time
+    to: Option<DateTime<Utc>>,
+}
+
+/// A restore executed on a drone
+#[derive(Serialize, ToSchema)]
+pub struct DroneRestoreResponse {
+    uuid: Uuid,
+    /// The name of the restored archive
+    archive: String,
+    /// The restored paths, empty if the whole archive was restored
+    paths: Vec<String>,
+    /// The directory on the drone the archive was extracted into
+    #[schema(example = "/srv/restore")]
+    target: String,
+    /// Whether the restore succeeded
+    success: bool,
+    /// The error that occurred during the restore
+    error: Option<String>,
+    /// The duration in seconds the restore took
+    duration: i64,
+    /// The point in time the restore was reported
+    created_at: DateTime<Utc>,
+}
+
+/// The restores reported by a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetDroneRestoresResponse {
+    restores: Vec<DroneRestoreResponse>,
+}
+
+/// Retrieve the restores that were executed with `borg-drone restore` on a drone
+///
+/// The restores are ordered by the point in time they were reported, starting with the oldest.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the restores of the drone", body = GetDroneRestoresResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid, GetDroneRestoresQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/restores")]
+pub async fn get_drone_restores(
+    path: Path<PathUuid>,
+    query: Query<GetDroneRestoresQuery>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetDroneRestoresResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let mut conditions: Vec<BoxedCondition<'_>> =
+        vec![DroneRestore::F.drone.equals(drone.as_ref()).boxed()];
+    if let Some(from) = query.from {
+        conditions.push(
+            DroneRestore::F
+                .created_at
+                .greater_or_equals(from.naive_utc())
+                .boxed(),
+        );
+    }
+    if let Some(to) = query.to {
+        conditions.push(DroneRestore::F.created_at.less(to.naive_utc()).boxed());
+    }
+
+    let restores = query!(&mut tx, DroneRestore)
+        .condition(DynamicCollection::and(conditions))
+        .order_asc(DroneRestore::F.created_at)
+        .all()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetDroneRestoresResponse {
+        restores: restores
+            .into_iter()
+            .map(|x| DroneRestoreResponse {
+                uuid: x.uuid,
+                archive: x.archive,
+                paths: x
+                    .paths
+                    .lines()
+                    .filter(|x| !x.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                target: x.target,
+                success: x.success,
+                error: x.error,
+                duration: x.duration,
+                created_at: DateTime::from_utc(x.created_at, Utc),
+            })
+            .collect(),
+    }))
+}
@@ -1,16 +1,18 @@
-use actix_web::web::{Data, Json, Path};
-use actix_web::{delete, get, post, HttpResponse};
+use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{delete, get, post, put, HttpResponse};
 use borgbackup::common::{CommonOptions, ListOptions};
 use chrono::{DateTime, Utc};
 use rand::distributions::{Alphanumeric, DistString};
 use rand::thread_rng;
-use rorm::{insert, query, Database, Model};
+use rorm::transaction::Transaction;
+use rorm::{and, insert, query, update, Database, Model};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-use crate::handler::{ApiError, ApiResult, PathUuid};
-use crate::models::{Drone, DroneInsert, DroneStats};
+use crate::handler::{deserialize_some, ApiError, ApiResult, PathUuid};
+use crate::models::{Drone, DroneInsert};
+use crate::modules::archives::ArchiveCache;
 
 /// The request to create a new drone
 #[derive(Deserialize, ToSchema)]
@@ -59,25 +61,8 @@ pub async fn create_drone(
         return Err(ApiError::InvalidName);
     }
 
-    let drone_ct = query!(&mut tx, (Drone::F.uuid.count(),))
-        .condition(Drone::F.name.equals(&req.name))
-        .one()
-        .await?
-        .0;
-
-    let repo_ct = query!(&mut tx, (Drone::F.repository.count(),))
-        .condition(Drone::F.repository.equals(&req.repository))
-        .one()
-        .await?
-        .0;
-
-    if drone_ct != 0 {
-        return Err(ApiError::NameAlreadyExists);
-    }
-
-    if repo_ct != 0 {
-        return Err(ApiError::RepositoryAlreadyExists);
-    }
+    check_name_available(&mut tx, &req.name, None).await?;
+    check_repository_available(&mut tx, &req.repository, None).await?;
 
     let token = Alphanumeric.sample_string(&mut thread_rng(), 255);
 
@@ -106,9 +91,54 @@ pub async fn create_drone(
     Ok(Json(CreateDroneResponse { uuid, token }))
 }
 
+/// Check that no other drone than `except` uses the name
+///
+/// Archived drones keep their name, the error tells the user how to free it.
+async fn check_name_available(
+    tx: &mut Transaction,
+    name: &str,
+    except: Option<&Uuid>,
+) -> ApiResult<()> {
+    let existing = query!(&mut *tx, (Drone::F.uuid, Drone::F.archived_at))
+        .condition(Drone::F.name.equals(name))
+        .optional()
+        .await?;
+
+    match existing {
+        Some((uuid, _)) if Some(&uuid) == except => Ok(()),
+        Some((_, Some(_))) => Err(ApiError::NameOfArchivedDrone),
+        Some((_, None)) => Err(ApiError::NameAlreadyExists),
+        None => Ok(()),
+    }
+}
+
+/// Check that no other drone than `except` uses the repository
+///
+/// Archived drones keep their repository, the error tells the user how to free it.
+async fn check_repository_available(
+    tx: &mut Transaction,
+    repository: &str,
+    except: Option<&Uuid>,
+) -> ApiResult<()> {
+    let existing = query!(&mut *tx, (Drone::F.uuid, Drone::F.archived_at))
+        .condition(Drone::F.repository.equals(repository))
+        .optional()
+        .await?;
+
+    match existing {
+        Some((uuid, _)) if Some(&uuid) == except => Ok(()),
+        Some((_, Some(_))) => Err(ApiError::RepositoryOfArchivedDrone),
+        Some((_, None)) => Err(ApiError::RepositoryAlreadyExists),
+        None => Ok(()),
+    }
+}
+
 /// The representation of a single drone.
 ///
 /// The parameter `token` is used as bearer token to authenticate the drone to the vinculum.
+///
+/// The hostname, os, kernel, versions and compression are the inventory of the host
+/// the drone last reported, they are not set if the drone never reported them.
 #[derive(Serialize, ToSchema)]
 pub struct GetDroneResponse {
     uuid: Uuid,
@@ -121,6 +151,22 @@ pub struct GetDroneResponse {
     repository: String,
     created_at: DateTime<Utc>,
     last_activity: Option<DateTime<Utc>>,
+    archived_at: Option<DateTime<Utc>>,
+    /// The capacity limit of the repository in bytes
+    #[schema(example = 107374182400_i64)]
+    capacity_limit: Option<i64>,
+    #[schema(example = "one-of-nine.example.com")]
+    hostname: Option<String>,
+    #[schema(example = "Debian GNU/Linux 12 (bookworm)")]
+    os: Option<String>,
+    #[schema(example = "6.1.0-10-amd64")]
+    kernel: Option<String>,
+    #[schema(example = "1.2.4")]
+    borg_version: Option<String>,
+    #[schema(example = "0.1.0")]
+    drone_version: Option<String>,
+    #[schema(example = "lz4")]
+    compression: Option<String>,
 }
 
 /// All available drones in the vinculum
@@ -129,7 +175,17 @@ pub struct GetAllDronesResponse {
     drones: Vec<GetDroneResponse>,
 }
 
+/// The query parameters to retrieve all drones
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetAllDronesQuery {
+    /// Include archived drones in the result
+    include_archived: Option<bool>,
+}
+
 /// Retrieve all drones from the vinculum
+///
+/// Archived drones are omitted unless `include_archived` is set.
 #[utoipa::path(
     tag = "Drone management",
     context_path = "/api/frontend/v1",
@@ -138,11 +194,22 @@ pub struct GetAllDronesResponse {
         (status = 400, description = "Client error", body = ApiErrorResponse),
         (status = 500, description = "Server error", body = ApiErrorResponse)
     ),
+    params(GetAllDronesQuery),
     security(("session_cookie" = [])),
 )]
 #[get("/drones")]
-pub async fn get_all_drones(db: Data<Database>) -> ApiResult<Json<GetAllDronesResponse>> {
-    let drones = query!(db.as_ref(), Drone).all().await?;
+pub async fn get_all_drones(
+    query: Query<GetAllDronesQuery>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetAllDronesResponse>> {
+    let drones = if query.include_archived.unwrap_or(false) {
+        query!(db.as_ref(), Drone).all().await?
+    } else {
+        query!(db.as_ref(), Drone)
+            .condition(Drone::F.archived_at.is_null())
+            .all()
+            .await?
+    };
 
     Ok(Json(GetAllDronesResponse {
         drones: drones
@@ -155,6 +222,14 @@ pub async fn get_all_drones(db: Data<Database>) -> ApiResult<Json<GetAllDronesRe
                 active: x.active,
                 created_at: DateTime::from_local(x.created_at, Utc),
                 last_activity: x.last_activity.map(|x| DateTime::from_local(x, Utc)),
+                archived_at: x.archived_at.map(|x| DateTime::from_local(x, Utc)),
+                capacity_limit: x.capacity_limit,
+                hostname: x.hostname,
+                os: x.os,
+                kernel: x.kernel,
+                borg_version: x.borg_version,
+                drone_version: x.drone_version,
+                compression: x.compression,
             })
             .collect(),
     }))
@@ -193,79 +268,207 @@ pub async fn get_drone(
         active: drone.active,
         created_at: DateTime::from_local(drone.created_at, Utc),
         last_activity: drone.last_activity.map(|x| DateTime::from_local(x, Utc)),
+        archived_at: drone.archived_at.map(|x| DateTime::from_local(x, Utc)),
+        capacity_limit: drone.capacity_limit,
+        hostname: drone.hostname,
+        os: drone.os,
+        kernel: drone.kernel,
+        borg_version: drone.borg_version,
+        drone_version: drone.drone_version,
+        compression: drone.compression,
     }))
 }
 
-/// A single stat record of a drone
-#[derive(Serialize, ToSchema)]
-pub struct DroneStat {
-    pre_hook_duration: Option<i64>,
-    post_hook_duration: Option<i64>,
-    create_duration: i64,
-    complete_duration: i64,
-    original_size: i64,
-    compressed_size: i64,
-    deduplicated_size: i64,
-    nfiles: i64,
-    created_at: DateTime<Utc>,
-}
-
-/// The stats of a drone
-#[derive(Serialize, ToSchema)]
-pub struct GetDroneStats {
-    stats: Vec<DroneStat>,
+/// The request to update an existing drone
+///
+/// Fields that are omitted are left unchanged.
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateDroneRequest {
+    #[schema(example = "one_of_nine")]
+    name: Option<String>,
+    #[schema(example = "user@example.com:server/1_of_9")]
+    repository: Option<String>,
+    #[schema(example = "super_secure_passphrase")]
+    passphrase: Option<String>,
+    /// The capacity limit of the repository in bytes.
+    ///
+    /// Set to `null` to remove the limit.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<i64>, example = 107374182400_i64)]
+    capacity_limit: Option<Option<i64>>,
 }
 
+/// Update a drone by its uuid
+///
+/// The `name` and `repository` parameters must be unique for all drones.
+///
+/// If the repository or passphrase is changed, the vinculum must be able to list the
+/// repository with the new settings.
 #[utoipa::path(
     tag = "Drone management",
     context_path = "/api/frontend/v1",
     responses(
-        (status = 200, description = "Retrieved the stats of the drone", body = GetDroneStats),
+        (status = 200, description = "Drone got updated"),
         (status = 400, description = "Client error", body = ApiErrorResponse),
         (status = 500, description = "Server error", body = ApiErrorResponse)
     ),
     params(PathUuid),
+    request_body = UpdateDroneRequest,
     security(("session_cookie" = [])),
 )]
-#[get("/drones/{uuid}/stats")]
-pub async fn get_drone_stats(
+#[put("/drones/{uuid}")]
+pub async fn update_drone(
     path: Path<PathUuid>,
+    req: Json<UpdateDroneRequest>,
     db: Data<Database>,
-) -> ApiResult<Json<GetDroneStats>> {
+    common_options: Data<CommonOptions>,
+    archive_cache: Data<ArchiveCache>,
+) -> ApiResult<HttpResponse> {
     let mut tx = db.start_transaction().await?;
 
-    let mut drone = query!(&mut tx, Drone)
+    let drone = query!(&mut tx, Drone)
         .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
         .optional()
         .await?
         .ok_or(ApiError::InvalidUuid)?;
 
-    Drone::F.stats.populate(&mut tx, &mut drone).await?;
+    if let Some(name) = &req.name {
+        if name.is_empty() {
+            return Err(ApiError::InvalidName);
+        }
+
+        check_name_available(&mut tx, name, Some(&drone.uuid)).await?;
+    }
+
+    if let Some(Some(capacity_limit)) = req.capacity_limit {
+        if capacity_limit <= 0 {
+            return Err(ApiError::InvalidCapacityLimit);
+        }
+    }
+
+    if let Some(repository) = &req.repository {
+        check_repository_available(&mut tx, repository, Some(&drone.uuid)).await?;
+    }
+
+    if let Ok(update) = update!(&mut tx, Drone)
+        .condition(Drone::F.uuid.equals(drone.uuid.as_ref()))
+        .begin_dyn_set()
+        .set_if(Drone::F.name, req.name.as_deref())
+        .set_if(Drone::F.repository, req.repository.as_deref())
+        .set_if(Drone::F.passphrase, req.passphrase.as_deref())
+        .set_if(Drone::F.capacity_limit, req.capacity_limit)
+        // The forecast is alerted again for the new limit
+        .set_if(Drone::F.capacity_alert, req.capacity_limit.map(|_| false))
+        .finish_dyn_set()
+    {
+        update.exec().await?;
+    }
+
+    if req.repository.is_some() || req.passphrase.is_some() {
+        borgbackup::asynchronous::list(
+            &ListOptions {
+                repository: req.repository.clone().unwrap_or(drone.repository),
+                passphrase: Some(req.passphrase.clone().unwrap_or(drone.passphrase)),
+            },
+            common_options.get_ref(),
+        )
+        .await?;
+    }
 
     tx.commit().await?;
 
-    Ok(Json(GetDroneStats {
-        stats: drone
-            .stats
-            .cached
-            .unwrap()
-            .into_iter()
-            .map(|x: DroneStats| DroneStat {
-                pre_hook_duration: x.pre_hook_duration,
-                post_hook_duration: x.post_hook_duration,
-                create_duration: x.create_duration,
-                complete_duration: x.complete_duration,
-                nfiles: x.nfiles,
-                original_size: x.original_size,
-                compressed_size: x.compressed_size,
-                deduplicated_size: x.deduplicated_size,
-                created_at: DateTime::from_utc(x.created_at, Utc),
-            })
-            .collect(),
-    }))
+    if req.repository.is_some() || req.passphrase.is_some() {
+        archive_cache.invalidate(&drone.uuid);
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Activate a drone by its uuid
+///
+/// Errors reported by an active drone are forwarded to the notifier.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Drone got activated"),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    security(("session_cookie" = [])),
+)]
+#[post("/drones/{uuid}/activate")]
+pub async fn activate_drone(path: Path<PathUuid>, db: Data<Database>) -> ApiResult<HttpResponse> {
+    set_drone_active(db.as_ref(), path.uuid, true).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Deactivate a drone by its uuid
+///
+/// Reports of an inactive drone are still recorded,
+/// but its errors are not forwarded to the notifier.
+/// The drone stays inactive until it is activated again.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Drone got deactivated"),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    security(("session_cookie" = [])),
+)]
+#[post("/drones/{uuid}/deactivate")]
+pub async fn deactivate_drone(path: Path<PathUuid>, db: Data<Database>) -> ApiResult<HttpResponse> {
+    set_drone_active(db.as_ref(), path.uuid, false).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn set_drone_active(db: &Database, uuid: Uuid, active: bool) -> ApiResult<()> {
+    let mut tx = db.start_transaction().await?;
+
+    let (archived_at,) = query!(&mut tx, (Drone::F.archived_at,))
+        .condition(Drone::F.uuid.equals(uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?;
+
+    if active && archived_at.is_some() {
+        return Err(ApiError::DroneArchived);
+    }
+
+    update!(&mut tx, Drone)
+        .condition(Drone::F.uuid.equals(uuid.as_ref()))
+        .set(Drone::F.active, active)
+        .set(Drone::F.deactivated, !active)
+        .exec()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// The query parameters to delete a drone
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteDroneQuery {
+    /// Remove the drone including all of its stats instead of archiving it
+    purge: Option<bool>,
 }
 
 /// Delete a drone by its uuid
+///
+/// By default, the drone is archived: It is deactivated, hidden from the list of drones and
+/// its token is rejected, but its stats are kept.
+/// An archived drone keeps its name and repository, so they can't be used by a new drone
+/// until the archived drone is renamed or purged.
+///
+/// If `purge` is set, the drone and all of its stats are removed.
 #[utoipa::path(
     tag = "Drone management",
     context_path = "/api/frontend/v1",
@@ -274,11 +477,15 @@ pub async fn get_drone_stats(
         (status = 400, description = "Client error", body = ApiErrorResponse),
         (status = 500, description = "Server error", body = ApiErrorResponse)
     ),
-    params(PathUuid),
+    params(PathUuid, DeleteDroneQuery),
     security(("session_cookie" = [])),
 )]
 #[delete("/drones/{uuid}")]
-pub async fn delete_drone(path: Path<PathUuid>, db: Data<Database>) -> ApiResult<HttpResponse> {
+pub async fn delete_drone(
+    path: Path<PathUuid>,
+    query: Query<DeleteDroneQuery>,
+    db: Data<Database>,
+) -> ApiResult<HttpResponse> {
     let mut tx = db.start_transaction().await?;
 
     let ct = query!(&mut tx, (Drone::F.uuid.count(),))
@@ -291,9 +498,21 @@ pub async fn delete_drone(path: Path<PathUuid>, db: Data<Database>) -> ApiResult
         return Err(ApiError::InvalidUuid);
     }
 
-    rorm::delete!(&mut tx, Drone)
-        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
-        .await?;
+    if query.purge.unwrap_or(false) {
+        rorm::delete!(&mut tx, Drone)
+            .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+            .await?;
+    } else {
+        update!(&mut tx, Drone)
+            .condition(and!(
+                Drone::F.uuid.equals(path.uuid.as_ref()),
+                Drone::F.archived_at.is_null()
+            ))
+            .set(Drone::F.active, false)
+            .set(Drone::F.archived_at, Some(Utc::now().naive_utc()))
+            .exec()
+            .await?;
+    }
 
     tx.commit().await?;
 
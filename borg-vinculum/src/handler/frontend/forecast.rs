@@ -0,0 +1,85 @@
+use actix_web::get;
+use actix_web::web::{Data, Json, Path};
+use borgbackup::common::CommonOptions;
+use chrono::{DateTime, Utc};
+use common::borg::info_repository;
+use rorm::{query, Database, Model};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::handler::{ApiError, ApiResult, PathUuid};
+use crate::models::Drone;
+use crate::modules::forecast::{estimate_growth, Forecast};
+
+/// The forecast of the size of the repository of a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetForecastResponse {
+    /// The current size of the repository in bytes
+    #[schema(example = 53687091200_i64)]
+    current_size: i64,
+    /// The estimated growth of the repository in bytes per day
+    #[schema(example = 1073741824.0)]
+    growth_per_day: f64,
+    /// The estimated size of the repository in 30 days
+    size_in_30_days: i64,
+    /// The estimated size of the repository in 90 days
+    size_in_90_days: i64,
+    /// The capacity limit of the repository in bytes
+    capacity_limit: Option<i64>,
+    /// The point in time the capacity limit is estimated to be reached
+    capacity_reached_at: Option<DateTime<Utc>>,
+}
+
+/// Forecast the size of the repository of a drone
+///
+/// The current size of the repository is retrieved using `borg info`,
+/// the growth is estimated with a linear regression over the reported stats of the drone.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Forecast of the repository", body = GetForecastResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/forecast")]
+pub async fn get_forecast(
+    path: Path<PathUuid>,
+    db: Data<Database>,
+    common_options: Data<CommonOptions>,
+) -> ApiResult<Json<GetForecastResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, Drone)
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?;
+
+    let growth = estimate_growth(&mut tx, &drone.uuid).await?;
+
+    tx.commit().await?;
+
+    let info = info_repository(&common_options, &drone.repository, &drone.passphrase).await?;
+
+    let forecast = Forecast::new(
+        info.cache.stats.unique_csize as i64,
+        growth,
+        drone.capacity_limit,
+        Utc::now().naive_utc(),
+    );
+
+    Ok(Json(GetForecastResponse {
+        current_size: forecast.current_size,
+        growth_per_day: forecast.growth_per_day,
+        size_in_30_days: forecast.size_in_days(30),
+        size_in_90_days: forecast.size_in_days(90),
+        capacity_limit: drone.capacity_limit,
+        capacity_reached_at: forecast
+            .capacity_reached_at
+            .map(|x| DateTime::from_utc(x, Utc)),
+    }))
+}
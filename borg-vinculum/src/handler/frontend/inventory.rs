@@ -0,0 +1,116 @@
+use actix_web::get;
+use actix_web::web::{Data, Json, Path, Query};
+use chrono::{DateTime, Utc};
+use rorm::conditions::{BoxedCondition, Condition, DynamicCollection};
+use rorm::{query, Database, Model};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::handler::{ApiError, ApiResult, PathUuid};
+use crate::models::{Drone, DroneInventory};
+
+/// The query parameters to retrieve the inventory history of a drone
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetInventoryQuery {
+    /// Only include inventories reported at or after this point in time
+    from: Option<DateTime<Utc>>,
+    /// Only include inventories reported before this point in time
+    to: Option<DateTime<Utc>>,
+}
+
+/// The inventory of the host of a drone
+#[derive(Serialize, ToSchema)]
+pub struct DroneInventoryResponse {
+    uuid: Uuid,
+    #[schema(example = "one-of-nine.example.com")]
+    hostname: Option<String>,
+    #[schema(example = "Debian GNU/Linux 12 (bookworm)")]
+    os: Option<String>,
+    #[schema(example = "6.1.0-10-amd64")]
+    kernel: Option<String>,
+    #[schema(example = "1.2.4")]
+    borg_version: Option<String>,
+    #[schema(example = "0.1.0")]
+    drone_version: Option<String>,
+    #[schema(example = "lz4")]
+    compression: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+/// The inventory history of a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetInventoryResponse {
+    inventories: Vec<DroneInventoryResponse>,
+}
+
+/// Retrieve the history of the inventory of the host of a drone
+///
+/// An entry is stored every time the drone reports an inventory that differs from the
+/// previous one, the latest values are part of the drone itself.
+/// The entries are ordered by the point in time they were reported, starting with the oldest.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the inventory history of the drone", body = GetInventoryResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid, GetInventoryQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/inventory")]
+pub async fn get_inventory(
+    path: Path<PathUuid>,
+    query: Query<GetInventoryQuery>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetInventoryResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let mut conditions: Vec<BoxedCondition<'_>> =
+        vec![DroneInventory::F.drone.equals(drone.as_ref()).boxed()];
+    if let Some(from) = query.from {
+        conditions.push(
+            DroneInventory::F
+                .created_at
+                .greater_or_equals(from.naive_utc())
+                .boxed(),
+        );
+    }
+    if let Some(to) = query.to {
+        conditions.push(DroneInventory::F.created_at.less(to.naive_utc()).boxed());
+    }
+
+    let inventories = query!(&mut tx, DroneInventory)
+        .condition(DynamicCollection::and(conditions))
+        .order_asc(DroneInventory::F.created_at)
+        .all()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetInventoryResponse {
+        inventories: inventories
+            .into_iter()
+            .map(|x| DroneInventoryResponse {
+                uuid: x.uuid,
+                hostname: x.hostname,
+                os: x.os,
+                kernel: x.kernel,
+                borg_version: x.borg_version,
+                drone_version: x.drone_version,
+                compression: x.compression,
+                created_at: DateTime::from_utc(x.created_at, Utc),
+            })
+            .collect(),
+    }))
+}
@@ -1,9 +1,31 @@
 //! All handler for the frontend are defined in this module
 
+pub use crate::handler::frontend::archives::*;
 pub use crate::handler::frontend::auth::*;
+pub use crate::handler::frontend::checks::*;
+pub use crate::handler::frontend::drills::*;
 pub use crate::handler::frontend::drones::*;
+pub use crate::handler::frontend::forecast::*;
+pub use crate::handler::frontend::inventory::*;
 pub use crate::handler::frontend::key::*;
+pub use crate::handler::frontend::prune::*;
+pub use crate::handler::frontend::repository::*;
+pub use crate::handler::frontend::restore::*;
+pub use crate::handler::frontend::rules::*;
+pub use crate::handler::frontend::stats::*;
+pub use crate::handler::frontend::warnings::*;
 
+mod archives;
 mod auth;
+mod checks;
+mod drills;
 mod drones;
+mod forecast;
+mod inventory;
 mod key;
+mod prune;
+mod repository;
+mod restore;
+mod rules;
+mod stats;
+mod warnings;
@@ -0,0 +1,241 @@
+use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{delete, get, put, HttpResponse};
+use chrono::{DateTime, Utc};
+use common::{GetPrunePolicyResponse, PrunePolicy};
+use rorm::conditions::{BoxedCondition, Condition, DynamicCollection};
+use rorm::fields::ForeignModelByField;
+use rorm::{insert, query, Database, Model};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::handler::{ApiError, ApiResult, PathUuid};
+use crate::models::{Drone, DronePrune, DronePrunePolicy, DronePrunePolicyInsert};
+
+/// Retrieve the retention policy of a drone
+///
+/// The policy is used by `borg-drone prune`, if the drone has no policy in its config.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the retention policy", body = GetPrunePolicyResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/prune-policy")]
+pub async fn get_prune_policy(
+    path: Path<PathUuid>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetPrunePolicyResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let policy = query!(&mut tx, DronePrunePolicy)
+        .condition(DronePrunePolicy::F.drone.equals(drone.as_ref()))
+        .optional()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetPrunePolicyResponse {
+        policy: policy.map(Into::into),
+    }))
+}
+
+/// Set the retention policy of a drone
+///
+/// An existing policy is replaced.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retention policy got set"),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    request_body = PrunePolicy,
+    security(("session_cookie" = [])),
+)]
+#[put("/drones/{uuid}/prune-policy")]
+pub async fn set_prune_policy(
+    path: Path<PathUuid>,
+    req: Json<PrunePolicy>,
+    db: Data<Database>,
+) -> ApiResult<HttpResponse> {
+    req.validate().map_err(ApiError::InvalidPrunePolicy)?;
+
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    rorm::delete!(&mut tx, DronePrunePolicy)
+        .condition(DronePrunePolicy::F.drone.equals(drone.as_ref()))
+        .await?;
+
+    let req = req.into_inner();
+    let count = |x: Option<u16>| x.map(i32::from);
+    insert!(&mut tx, DronePrunePolicyInsert)
+        .return_nothing()
+        .single(&DronePrunePolicyInsert {
+            uuid: Uuid::new_v4(),
+            drone: ForeignModelByField::Key(drone),
+            keep_within: req.keep_within,
+            keep_hourly: count(req.keep_hourly),
+            keep_daily: count(req.keep_daily),
+            keep_weekly: count(req.keep_weekly),
+            keep_monthly: count(req.keep_monthly),
+            keep_yearly: count(req.keep_yearly),
+        })
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Remove the retention policy of a drone
+///
+/// Drones without a policy in their config can't prune anymore.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retention policy got removed"),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    security(("session_cookie" = [])),
+)]
+#[delete("/drones/{uuid}/prune-policy")]
+pub async fn delete_prune_policy(
+    path: Path<PathUuid>,
+    db: Data<Database>,
+) -> ApiResult<HttpResponse> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    rorm::delete!(&mut tx, DronePrunePolicy)
+        .condition(DronePrunePolicy::F.drone.equals(drone.as_ref()))
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// The query parameters to retrieve the prunes of a drone
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetPrunesQuery {
+    /// Only include prunes reported at or after this point in time
+    from: Option<DateTime<Utc>>,
+    /// Only include prunes reported before this point in time
+    to: Option<DateTime<Utc>>,
+}
+
+/// The stats of a prune of the repository
+#[derive(Serialize, ToSchema)]
+pub struct RepositoryPrune {
+    uuid: Uuid,
+    /// The number of archives that were pruned
+    pruned: i64,
+    /// The number of archives that were kept
+    kept: i64,
+    /// The duration in seconds the prune took
+    duration: i64,
+    created_at: DateTime<Utc>,
+}
+
+/// The prunes of the repository of a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetPrunesResponse {
+    prunes: Vec<RepositoryPrune>,
+}
+
+/// Retrieve the prunes reported by a drone
+///
+/// The prunes are ordered by the point in time they were reported, starting with the oldest.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the prunes of the drone", body = GetPrunesResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid, GetPrunesQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/prunes")]
+pub async fn get_prunes(
+    path: Path<PathUuid>,
+    query: Query<GetPrunesQuery>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetPrunesResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let mut conditions: Vec<BoxedCondition<'_>> =
+        vec![DronePrune::F.drone.equals(drone.as_ref()).boxed()];
+    if let Some(from) = query.from {
+        conditions.push(
+            DronePrune::F
+                .created_at
+                .greater_or_equals(from.naive_utc())
+                .boxed(),
+        );
+    }
+    if let Some(to) = query.to {
+        conditions.push(DronePrune::F.created_at.less(to.naive_utc()).boxed());
+    }
+
+    let prunes = query!(&mut tx, DronePrune)
+        .condition(DynamicCollection::and(conditions))
+        .order_asc(DronePrune::F.created_at)
+        .all()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetPrunesResponse {
+        prunes: prunes
+            .into_iter()
+            .map(|x| RepositoryPrune {
+                uuid: x.uuid,
+                pruned: x.pruned,
+                kept: x.kept,
+                duration: x.duration,
+                created_at: DateTime::from_utc(x.created_at, Utc),
+            })
+            .collect(),
+    }))
+}
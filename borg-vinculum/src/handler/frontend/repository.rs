@@ -0,0 +1,124 @@
+use actix_web::get;
+use actix_web::web::{Data, Json, Path, Query};
+use chrono::{DateTime, Utc};
+use rorm::conditions::{BoxedCondition, Condition, DynamicCollection};
+use rorm::{query, Database, Model};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::handler::{ApiError, ApiResult, PathUuid};
+use crate::models::{Drone, RepositorySnapshot};
+
+/// The query parameters to retrieve the snapshots of a repository
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetRepositoryQuery {
+    /// Only include snapshots taken at or after this point in time
+    from: Option<DateTime<Utc>>,
+    /// Only include snapshots taken before this point in time
+    to: Option<DateTime<Utc>>,
+}
+
+/// A snapshot of the information about a repository
+#[derive(Serialize, ToSchema)]
+pub struct RepositorySnapshotResponse {
+    /// The number of archives in the repository
+    archives: i64,
+    /// Number of chunks
+    total_chunks: i64,
+    /// Number of unique chunks
+    total_unique_chunks: i64,
+    /// Total uncompressed size of all chunks multiplied with their reference counts
+    total_size: i64,
+    /// Total compressed and encrypted size of all chunks multiplied with their reference counts
+    total_csize: i64,
+    /// Uncompressed size of all chunks
+    unique_size: i64,
+    /// Compressed and encrypted size of all chunks, the size on disk
+    unique_csize: i64,
+    /// The point in time the repository was last modified
+    last_modified: DateTime<Utc>,
+    /// The point in time the snapshot was taken
+    created_at: DateTime<Utc>,
+}
+
+/// The snapshots of the repository of a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetRepositoryResponse {
+    snapshots: Vec<RepositorySnapshotResponse>,
+}
+
+/// Retrieve the snapshots of the repository of a drone
+///
+/// The snapshots are taken periodically using `borg info` and are ordered by the point in time
+/// they were taken, starting with the oldest.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the snapshots of the repository", body = GetRepositoryResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid, GetRepositoryQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/repository")]
+pub async fn get_repository(
+    path: Path<PathUuid>,
+    query: Query<GetRepositoryQuery>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetRepositoryResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let mut conditions: Vec<BoxedCondition<'_>> =
+        vec![RepositorySnapshot::F.drone.equals(drone.as_ref()).boxed()];
+    if let Some(from) = query.from {
+        conditions.push(
+            RepositorySnapshot::F
+                .created_at
+                .greater_or_equals(from.naive_utc())
+                .boxed(),
+        );
+    }
+    if let Some(to) = query.to {
+        conditions.push(
+            RepositorySnapshot::F
+                .created_at
+                .less(to.naive_utc())
+                .boxed(),
+        );
+    }
+
+    let snapshots = query!(&mut tx, RepositorySnapshot)
+        .condition(DynamicCollection::and(conditions))
+        .order_asc(RepositorySnapshot::F.created_at)
+        .all()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetRepositoryResponse {
+        snapshots: snapshots
+            .into_iter()
+            .map(|x| RepositorySnapshotResponse {
+                archives: x.archives,
+                total_chunks: x.total_chunks,
+                total_unique_chunks: x.total_unique_chunks,
+                total_size: x.total_size,
+                total_csize: x.total_csize,
+                unique_size: x.unique_size,
+                unique_csize: x.unique_csize,
+                last_modified: DateTime::from_utc(x.last_modified, Utc),
+                created_at: DateTime::from_utc(x.created_at, Utc),
+            })
+            .collect(),
+    }))
+}
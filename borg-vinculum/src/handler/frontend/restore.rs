@@ -0,0 +1,386 @@
+use std::sync::Arc;
+
+use actix_toolbox::tb_middleware::Session;
+use actix_web::get;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{Bytes, Data, Json, Path, Query};
+use actix_web::{HttpRequest, HttpResponse};
+use borgbackup::common::CommonOptions;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use log::info;
+use rorm::fields::ForeignModelByField;
+use rorm::{insert, query, Database, Model};
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::handler::{ApiError, ApiResult};
+use crate::models::{Account, Drone, RestoreAction, RestoreEventInsert};
+use crate::modules::archives::{parent, ArchiveCache, CachedDirectory};
+use crate::modules::borg::{archive_item, export_tar, extract_file, list_directory};
+
+/// The path parameters of an archive of a drone
+#[derive(Deserialize, IntoParams)]
+pub struct PathArchive {
+    /// The uuid of the drone
+    uuid: Uuid,
+    /// The name of the archive
+    name: String,
+}
+
+/// The query parameters to access a path in an archive
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ArchivePathQuery {
+    /// The path in the archive
+    ///
+    /// Leading and trailing slashes are ignored.
+    #[serde(default)]
+    path: String,
+}
+
+/// An item in an archive
+#[derive(Serialize, ToSchema)]
+pub struct ArchiveContentItem {
+    /// The type of the item, e.g. `-` for regular files, `d` for directories, `l` for symlinks
+    #[schema(example = "-")]
+    kind: String,
+    /// The file mode
+    #[schema(example = "-rw-r--r--")]
+    mode: String,
+    /// The name of the owning user
+    user: String,
+    /// The name of the owning group
+    group: String,
+    /// The path of the item in the archive
+    #[schema(example = "etc/nginx/nginx.conf")]
+    path: String,
+    /// The target of a link, empty for all other items
+    link_target: String,
+    /// The modification time
+    mtime: DateTime<Utc>,
+    /// The size in bytes
+    size: u64,
+}
+
+/// The contents of a directory in an archive
+#[derive(Serialize, ToSchema)]
+pub struct GetArchiveContentsResponse {
+    /// The listed directory, empty for the root of the archive
+    path: String,
+    /// The items in the directory
+    items: Vec<ArchiveContentItem>,
+}
+
+/// The maximum length of the paths that are recorded for an access
+const MAX_RECORDED_PATH_LENGTH: usize = 4096;
+
+/// Retrieve the account of the session and check whether it may access the contents of archives
+async fn restore_account(session: &Session, db: &Database, config: &Config) -> ApiResult<Account> {
+    let uuid: Uuid = session.get("uuid")?.ok_or(ApiError::SessionCorrupt)?;
+
+    let account = query!(db, Account)
+        .condition(Account::F.uuid.equals(uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::SessionCorrupt)?;
+
+    if !config.restore.allowed_accounts.contains(&account.username) {
+        return Err(ApiError::RestoreForbidden);
+    }
+
+    Ok(account)
+}
+
+/// Validate the requested archive and path and retrieve the drone
+async fn restore_target(db: &Database, path: &PathArchive) -> ApiResult<Drone> {
+    if path.name.is_empty() || path.name.contains('/') {
+        return Err(ApiError::InvalidArchive);
+    }
+
+    query!(db, Drone)
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)
+}
+
+/// Record an access to the contents of an archive
+async fn record_restore_event(
+    db: &Database,
+    account: &Account,
+    drone: &Drone,
+    archive: &str,
+    path: &str,
+    action: RestoreAction,
+) -> ApiResult<()> {
+    if path.len() > MAX_RECORDED_PATH_LENGTH {
+        return Err(ApiError::InvalidPath);
+    }
+
+    info!(
+        "Account {account} accessed archive {archive} of drone {drone}: {action:?} {path:?}",
+        account = account.username,
+        drone = drone.name,
+    );
+
+    insert!(db, RestoreEventInsert)
+        .return_nothing()
+        .single(&RestoreEventInsert {
+            uuid: Uuid::new_v4(),
+            account: ForeignModelByField::Key(account.uuid),
+            drone: ForeignModelByField::Key(drone.uuid),
+            archive: archive.to_string(),
+            path: path.to_string(),
+            action,
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Retrieve a directory of an archive of a drone from the cache or list it
+///
+/// Returns `None` if there is no such directory in the archive.
+async fn archive_directory(
+    common_options: &CommonOptions,
+    cache: &ArchiveCache,
+    drone: &Drone,
+    archive: &str,
+    dir: &str,
+) -> ApiResult<Option<Arc<CachedDirectory>>> {
+    if let Some(cached) = cache.get_directory(&drone.uuid, archive, dir) {
+        return Ok(Some(cached));
+    }
+
+    let Some(items) = list_directory(
+        common_options,
+        &drone.repository,
+        archive,
+        &drone.passphrase,
+        dir,
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(cache.insert_directory(
+        drone.uuid,
+        archive.to_string(),
+        dir.to_string(),
+        items,
+    )))
+}
+
+/// List the contents of a directory in an archive
+///
+/// Only the direct children of the directory are returned.
+/// Every directory is listed on its own and cached like the archives of a drone.
+///
+/// This is only allowed for the accounts configured in `Restore.AllowedAccounts`.
+/// Every access is logged.
+#[utoipa::path(
+    tag = "Restore",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the contents of the directory", body = GetArchiveContentsResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathArchive, ArchivePathQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/archives/{name}/contents")]
+pub async fn get_archive_contents(
+    path: Path<PathArchive>,
+    query: Query<ArchivePathQuery>,
+    session: Session,
+    db: Data<Database>,
+    config: Data<Config>,
+    common_options: Data<CommonOptions>,
+    cache: Data<ArchiveCache>,
+) -> ApiResult<Json<GetArchiveContentsResponse>> {
+    let account = restore_account(&session, &db, &config).await?;
+    let drone = restore_target(&db, &path).await?;
+    let dir = query.path.trim_matches('/');
+
+    let directory = archive_directory(&common_options, &cache, &drone, &path.name, dir)
+        .await?
+        .ok_or(ApiError::InvalidPath)?;
+
+    record_restore_event(&db, &account, &drone, &path.name, dir, RestoreAction::List).await?;
+
+    Ok(Json(GetArchiveContentsResponse {
+        path: dir.to_string(),
+        items: directory
+            .items
+            .iter()
+            .map(|x| ArchiveContentItem {
+                kind: x.kind.clone(),
+                mode: x.mode.clone(),
+                user: x.user.clone(),
+                group: x.group.clone(),
+                path: x.path.clone(),
+                link_target: x.linktarget.clone(),
+                mtime: DateTime::from_utc(x.mtime, Utc),
+                size: x.size,
+            })
+            .collect(),
+    }))
+}
+
+/// Download a single file of an archive
+///
+/// The file is extracted using `borg extract --stdout` and streamed to the client.
+///
+/// This is only allowed for the accounts configured in `Restore.AllowedAccounts`.
+/// Every download is logged.
+#[utoipa::path(
+    tag = "Restore",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "The contents of the file", content_type = "application/octet-stream"),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathArchive, ArchivePathQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/archives/{name}/file")]
+pub async fn download_archive_file(
+    path: Path<PathArchive>,
+    query: Query<ArchivePathQuery>,
+    session: Session,
+    db: Data<Database>,
+    config: Data<Config>,
+    common_options: Data<CommonOptions>,
+    cache: Data<ArchiveCache>,
+) -> ApiResult<HttpResponse> {
+    let account = restore_account(&session, &db, &config).await?;
+    let drone = restore_target(&db, &path).await?;
+    let file = query.path.trim_matches('/');
+
+    if file.is_empty() {
+        return Err(ApiError::InvalidPath);
+    }
+
+    // Only regular files can be extracted to stdout
+    let item = match cache.get_directory(&drone.uuid, &path.name, parent(file)) {
+        Some(directory) => directory.item(file).cloned(),
+        None => {
+            archive_item(
+                &common_options,
+                &drone.repository,
+                &path.name,
+                &drone.passphrase,
+                file,
+            )
+            .await?
+        }
+    }
+    .filter(|x| x.kind == "-")
+    .ok_or(ApiError::InvalidPath)?;
+
+    record_restore_event(
+        &db,
+        &account,
+        &drone,
+        &path.name,
+        file,
+        RestoreAction::Download,
+    )
+    .await?;
+
+    let stream = extract_file(
+        &common_options,
+        &drone.repository,
+        &path.name,
+        &drone.passphrase,
+        file,
+    )?;
+
+    let filename = file.rsplit('/').next().unwrap_or(file).to_string();
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(filename)],
+        })
+        .no_chunking(item.size)
+        .streaming(stream.map_ok(Bytes::from)))
+}
+
+/// Export an archive as tar
+///
+/// The archive is exported using `borg export-tar` and streamed to the client.
+/// Specify `path` multiple times to only export these paths.
+/// If the client disconnects, borg is stopped.
+///
+/// This is only allowed for the accounts configured in `Restore.AllowedAccounts`.
+/// Every export is logged.
+#[utoipa::path(
+    tag = "Restore",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "The archive as tar", content_type = "application/x-tar"),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(
+        PathArchive,
+        ("path" = Option<Vec<String>>, Query, description = "Only export these paths of the archive"),
+    ),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/archives/{name}/tar")]
+pub async fn export_archive_tar(
+    path: Path<PathArchive>,
+    req: HttpRequest,
+    session: Session,
+    db: Data<Database>,
+    config: Data<Config>,
+    common_options: Data<CommonOptions>,
+) -> ApiResult<HttpResponse> {
+    let account = restore_account(&session, &db, &config).await?;
+    let drone = restore_target(&db, &path).await?;
+
+    // serde_urlencoded doesn't support repeated keys
+    let paths: Vec<String> = form_urlencoded::parse(req.query_string().as_bytes())
+        .filter(|(key, _)| key == "path")
+        .map(|(_, value)| value.trim_matches('/').to_string())
+        .collect();
+    if paths.iter().any(|x| x.is_empty()) {
+        return Err(ApiError::InvalidPath);
+    }
+
+    record_restore_event(
+        &db,
+        &account,
+        &drone,
+        &path.name,
+        &paths.join("\n"),
+        RestoreAction::ExportTar,
+    )
+    .await?;
+
+    let stream = export_tar(
+        &common_options,
+        &drone.repository,
+        &path.name,
+        &drone.passphrase,
+        &paths.iter().map(String::as_str).collect::<Vec<_>>(),
+    )?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-tar")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!("{}.tar", path.name))],
+        })
+        .streaming(stream.map_ok(Bytes::from)))
+}
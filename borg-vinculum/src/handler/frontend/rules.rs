@@ -0,0 +1,278 @@
+use actix_web::web::{Data, Json, Path};
+use actix_web::{delete, get, post, HttpResponse};
+use chrono::{DateTime, Utc};
+use rorm::fields::ForeignModelByField;
+use rorm::{and, insert, query, update, Database, Model};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::handler::{ApiError, ApiResult, PathUuid};
+use crate::models::{
+    Drone, DroneRule, DroneRuleInsert, DroneRuleViolation, RuleField, RuleOperator,
+};
+
+/// The path parameters of a single rule of a drone
+#[derive(Deserialize, IntoParams)]
+pub struct PathRule {
+    /// The uuid of the drone
+    uuid: Uuid,
+    /// The uuid of the rule
+    rule_uuid: Uuid,
+}
+
+/// The request to create a new rule for a drone
+///
+/// The rule is violated, if the reported value does not satisfy
+/// the comparison `<field> <operator> <value>`.
+#[derive(Deserialize, ToSchema)]
+pub struct CreateRuleRequest {
+    field: RuleField,
+    operator: RuleOperator,
+    #[schema(example = 10000)]
+    value: i64,
+}
+
+/// The response of a request to create a rule
+#[derive(Serialize, ToSchema)]
+pub struct CreateRuleResponse {
+    uuid: Uuid,
+}
+
+/// Create a new rule for a drone
+///
+/// The rule is evaluated every time the drone reports its stats.
+#[utoipa::path(
+    tag = "Rule management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Created new rule", body = CreateRuleResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    request_body = CreateRuleRequest,
+    security(("session_cookie" = [])),
+)]
+#[post("/drones/{uuid}/rules")]
+pub async fn create_rule(
+    path: Path<PathUuid>,
+    req: Json<CreateRuleRequest>,
+    db: Data<Database>,
+) -> ApiResult<Json<CreateRuleResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let uuid = insert!(&mut tx, DroneRuleInsert)
+        .return_primary_key()
+        .single(&DroneRuleInsert {
+            uuid: Uuid::new_v4(),
+            drone: ForeignModelByField::Key(drone),
+            field: req.field,
+            operator: req.operator,
+            value: req.value,
+        })
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(CreateRuleResponse { uuid }))
+}
+
+/// A single rule of a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetRuleResponse {
+    uuid: Uuid,
+    field: RuleField,
+    operator: RuleOperator,
+    #[schema(example = 10000)]
+    value: i64,
+    created_at: DateTime<Utc>,
+}
+
+/// All rules of a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetAllRulesResponse {
+    rules: Vec<GetRuleResponse>,
+}
+
+/// Retrieve all rules of a drone
+#[utoipa::path(
+    tag = "Rule management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved all rules of the drone", body = GetAllRulesResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/rules")]
+pub async fn get_all_rules(
+    path: Path<PathUuid>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetAllRulesResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let rules = query!(&mut tx, DroneRule)
+        .condition(and!(
+            DroneRule::F.drone.equals(drone.as_ref()),
+            DroneRule::F.deleted_at.is_null()
+        ))
+        .order_asc(DroneRule::F.created_at)
+        .all()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetAllRulesResponse {
+        rules: rules
+            .into_iter()
+            .map(|x| GetRuleResponse {
+                uuid: x.uuid,
+                field: x.field,
+                operator: x.operator,
+                value: x.value,
+                created_at: DateTime::from_utc(x.created_at, Utc),
+            })
+            .collect(),
+    }))
+}
+
+/// Delete a rule of a drone
+///
+/// The rule is no longer evaluated, its recorded violations are kept.
+#[utoipa::path(
+    tag = "Rule management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Rule got deleted"),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathRule),
+    security(("session_cookie" = [])),
+)]
+#[delete("/drones/{uuid}/rules/{rule_uuid}")]
+pub async fn delete_rule(path: Path<PathRule>, db: Data<Database>) -> ApiResult<HttpResponse> {
+    let mut tx = db.start_transaction().await?;
+
+    let ct = query!(&mut tx, (DroneRule::F.uuid.count(),))
+        .condition(and!(
+            DroneRule::F.uuid.equals(path.rule_uuid.as_ref()),
+            DroneRule::F.drone.equals(path.uuid.as_ref()),
+            DroneRule::F.deleted_at.is_null()
+        ))
+        .one()
+        .await?
+        .0;
+
+    if ct == 0 {
+        return Err(ApiError::InvalidUuid);
+    }
+
+    update!(&mut tx, DroneRule)
+        .condition(DroneRule::F.uuid.equals(path.rule_uuid.as_ref()))
+        .set(DroneRule::F.deleted_at, Some(Utc::now().naive_utc()))
+        .exec()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// A single violation of a rule
+#[derive(Serialize, ToSchema)]
+pub struct RuleViolation {
+    uuid: Uuid,
+    /// The violated rule, it may have been deleted since
+    rule: Uuid,
+    /// The stats that violated the rule
+    ///
+    /// Not set once the stats were aggregated or for violations recorded before it was tracked.
+    stats: Option<Uuid>,
+    /// The reported value that violated the rule
+    #[schema(example = 42)]
+    value: i64,
+    created_at: DateTime<Utc>,
+}
+
+/// The recorded violations of the rules of a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetRuleViolationsResponse {
+    violations: Vec<RuleViolation>,
+}
+
+/// Retrieve the recorded violations of all rules of a drone
+///
+/// The violations are ordered by the point in time they occurred, starting with the newest.
+#[utoipa::path(
+    tag = "Rule management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the violations", body = GetRuleViolationsResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/rules/violations")]
+pub async fn get_rule_violations(
+    path: Path<PathUuid>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetRuleViolationsResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let violations = query!(
+        &mut tx,
+        (
+            DroneRuleViolation::F.uuid,
+            DroneRuleViolation::F.rule,
+            DroneRuleViolation::F.stats,
+            DroneRuleViolation::F.value,
+            DroneRuleViolation::F.created_at,
+        )
+    )
+    .condition(DroneRuleViolation::F.rule.drone.equals(drone.as_ref()))
+    .order_desc(DroneRuleViolation::F.created_at)
+    .all()
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetRuleViolationsResponse {
+        violations: violations
+            .into_iter()
+            .map(|(uuid, rule, stats, value, created_at)| RuleViolation {
+                uuid,
+                rule: *rule.key(),
+                stats: stats.map(|x| *x.key()),
+                value,
+                created_at: DateTime::from_utc(created_at, Utc),
+            })
+            .collect(),
+    }))
+}
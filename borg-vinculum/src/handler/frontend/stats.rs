@@ -0,0 +1,629 @@
+use actix_web::get;
+use actix_web::web::{Data, Json, Path, Query};
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use common::{ChangedPath, RepositoryTotals};
+use futures::StreamExt;
+use rorm::conditions::{BoxedCondition, Condition, DynamicCollection};
+use rorm::{query, Database, Model};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::handler::{ApiError, ApiResult, PathUuid};
+use crate::models::{AggregationPeriod, Drone, DroneStats, DroneStatsAggregate, DroneStatsSample};
+use crate::modules::stats::{
+    start_of_day, start_of_hour, start_of_week, StatsAccumulator, StatsSample, ValueAccumulator,
+};
+
+/// The number of entries that are returned if no limit is specified
+const DEFAULT_STATS_LIMIT: u64 = 1000;
+/// The maximum number of entries that can be requested at once
+const MAX_STATS_LIMIT: u64 = 10000;
+/// The number of entries that are returned with their changed paths if no limit is specified
+const DEFAULT_STATS_WITH_PATHS_LIMIT: u64 = 100;
+/// The maximum number of entries that can be requested with their changed paths at once
+///
+/// The changed paths of a single entry may take up to 256 KiB.
+const MAX_STATS_WITH_PATHS_LIMIT: u64 = 100;
+
+/// The query parameters to retrieve the stats of a drone
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetDroneStatsQuery {
+    /// Only include stats collected at or after this point in time
+    from: Option<DateTime<Utc>>,
+    /// Only include stats collected before this point in time
+    to: Option<DateTime<Utc>>,
+    /// The maximum number of entries to return.
+    ///
+    /// Defaults to 1000, can be at most 10000.
+    /// If `include_paths` is set, it defaults to 100 and can be at most 100.
+    limit: Option<u64>,
+    /// Continue after this record.
+    ///
+    /// Use the `next_cursor` of the previous response.
+    cursor: Option<String>,
+    /// Include the changed paths of the records, if the drone reported them.
+    #[serde(default)]
+    include_paths: bool,
+}
+
+/// A single stat record of a drone
+///
+/// Old stats are aggregated per day or week.
+/// For aggregates, `period` is set, `created_at` is the start of the period,
+/// `count` is the number of aggregated records and all values are averages.
+///
+/// `anomaly` is set if the record deviated from the baseline of the previous records.
+/// `warnings` is the number of warnings borg logged, it is always 0 for aggregates.
+///
+/// The number of added, modified and unchanged files is only set for records of drones
+/// that report them, never for aggregates.
+/// `changed_paths` is only set if requested and the drone reported the paths,
+/// `changed_paths_truncated` is set if the drone reported only a part of them.
+///
+/// The archive values and the totals of the repository after the archive was created
+/// are only set for records of drones that report them, never for aggregates.
+/// `archive_start` and `archive_end` are in the local time of the drone.
+///
+/// `created_at` is the point in time the drone finished the run, corrected by the
+/// skew of its clock, or the point in time the stats were received by the vinculum,
+/// if the drone didn't report it.
+/// The points in time the run and its hooks were started and finished are reported
+/// by the drone and are not corrected.
+/// `clock_skew` is the deviation in seconds of the clock of the drone, positive if it is ahead.
+/// These values and `received_at` are never set for aggregates.
+#[derive(Serialize, ToSchema)]
+pub struct DroneStat {
+    period: Option<AggregationPeriod>,
+    count: i64,
+    anomaly: bool,
+    warnings: i64,
+    files_added: Option<i64>,
+    files_modified: Option<i64>,
+    files_unchanged: Option<i64>,
+    changed_paths: Option<Vec<ChangedPath>>,
+    changed_paths_truncated: bool,
+    archive_name: Option<String>,
+    archive_id: Option<String>,
+    archive_start: Option<NaiveDateTime>,
+    archive_end: Option<NaiveDateTime>,
+    repository: Option<RepositoryTotals>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+    pre_hook_started_at: Option<DateTime<Utc>>,
+    pre_hook_finished_at: Option<DateTime<Utc>>,
+    post_hook_started_at: Option<DateTime<Utc>>,
+    post_hook_finished_at: Option<DateTime<Utc>>,
+    clock_skew: Option<i64>,
+    received_at: Option<DateTime<Utc>>,
+    pre_hook_duration: Option<i64>,
+    post_hook_duration: Option<i64>,
+    create_duration: i64,
+    complete_duration: i64,
+    original_size: i64,
+    compressed_size: i64,
+    deduplicated_size: i64,
+    nfiles: i64,
+    created_at: DateTime<Utc>,
+}
+
+/// The stats of a drone
+///
+/// If there are more stats available, `next_cursor` is set.
+/// It identifies the last returned record and is opaque to clients.
+#[derive(Serialize, ToSchema)]
+pub struct GetDroneStats {
+    stats: Vec<DroneStat>,
+    next_cursor: Option<String>,
+}
+
+/// The position of a record in the stats of a drone
+///
+/// Records are ordered by the point in time they were collected,
+/// records that were collected at the same time by their primary key.
+struct StatsCursor {
+    created_at: NaiveDateTime,
+    uuid: Uuid,
+}
+
+impl StatsCursor {
+    /// Parse a cursor that was returned by [StatsCursor::format]
+    fn parse(cursor: &str) -> ApiResult<Self> {
+        let (created_at, uuid) = cursor.split_once('_').ok_or(ApiError::InvalidCursor)?;
+
+        Ok(Self {
+            created_at: DateTime::parse_from_rfc3339(created_at)
+                .map_err(|_| ApiError::InvalidCursor)?
+                .naive_utc(),
+            uuid: Uuid::parse_str(uuid).map_err(|_| ApiError::InvalidCursor)?,
+        })
+    }
+
+    /// Format the cursor to continue after the given sample
+    fn format(sample: &StatsSample) -> String {
+        let created_at = DateTime::<Utc>::from_utc(sample.created_at, Utc)
+            .to_rfc3339_opts(SecondsFormat::AutoSi, true);
+        format!("{created_at}_{}", sample.uuid)
+    }
+}
+
+/// Retrieve the stats of a drone
+///
+/// The stats are ordered by the point in time they were collected, starting with the oldest.
+/// For drones that report it, this is the end of the run, corrected by the skew of their clock.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the stats of the drone", body = GetDroneStats),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid, GetDroneStatsQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/stats")]
+pub async fn get_drone_stats(
+    path: Path<PathUuid>,
+    query: Query<GetDroneStatsQuery>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetDroneStats>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let limit = if query.include_paths {
+        get_limit(
+            query.limit,
+            DEFAULT_STATS_WITH_PATHS_LIMIT,
+            MAX_STATS_WITH_PATHS_LIMIT,
+        )?
+    } else {
+        get_limit(query.limit, DEFAULT_STATS_LIMIT, MAX_STATS_LIMIT)?
+    };
+    let cursor = query
+        .cursor
+        .as_deref()
+        .map(StatsCursor::parse)
+        .transpose()?;
+
+    let from = query.from.map(|x| x.naive_utc());
+    let to = query.to.map(|x| x.naive_utc());
+
+    let aggregates = query!(&mut tx, DroneStatsAggregate)
+        .condition(aggregate_condition(&drone, from, to, cursor.as_ref()))
+        .order_asc(DroneStatsAggregate::F.created_at)
+        .order_asc(DroneStatsAggregate::F.uuid)
+        .limit(limit + 1)
+        .all()
+        .await?;
+
+    let raw = query!(&mut tx, DroneStatsSample)
+        .condition(stats_condition(&drone, from, to, cursor.as_ref()))
+        .order_asc(DroneStats::F.collected_at)
+        .order_asc(DroneStats::F.uuid)
+        .limit(limit + 1)
+        .all()
+        .await?;
+
+    // The changed paths may be large, so they are only queried if requested
+    let mut changed_paths: HashMap<Uuid, String> = if query.include_paths {
+        query!(&mut tx, (DroneStats::F.uuid, DroneStats::F.changed_paths))
+            .condition(stats_condition(&drone, from, to, cursor.as_ref()))
+            .order_asc(DroneStats::F.collected_at)
+            .order_asc(DroneStats::F.uuid)
+            .limit(limit + 1)
+            .all()
+            .await?
+            .into_iter()
+            .filter_map(|(uuid, paths)| Some((uuid, paths?)))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    tx.commit().await?;
+
+    let mut stats: Vec<StatsSample> = aggregates
+        .into_iter()
+        .map(StatsSample::from)
+        .chain(raw.into_iter().map(StatsSample::from))
+        .collect();
+    stats.sort_by_key(|x| (x.created_at, x.uuid));
+    stats.truncate(limit as usize + 1);
+
+    let next_cursor = if stats.len() as u64 > limit {
+        stats.pop();
+        stats.last().map(StatsCursor::format)
+    } else {
+        None
+    };
+
+    for stat in &mut stats {
+        stat.changed_paths = changed_paths.remove(&stat.uuid);
+    }
+
+    let utc = |x| DateTime::from_utc(x, Utc);
+    Ok(Json(GetDroneStats {
+        stats: stats
+            .into_iter()
+            .map(|x| DroneStat {
+                period: x.period,
+                count: x.count,
+                anomaly: x.anomaly,
+                warnings: x.warnings,
+                files_added: x.files_added,
+                files_modified: x.files_modified,
+                files_unchanged: x.files_unchanged,
+                changed_paths: x.changed_paths.and_then(|x| serde_json::from_str(&x).ok()),
+                changed_paths_truncated: x.changed_paths_truncated,
+                archive_name: x.archive_name,
+                archive_id: x.archive_id,
+                archive_start: x.archive_start,
+                archive_end: x.archive_end,
+                repository: x.repository,
+                started_at: x.started_at.map(utc),
+                finished_at: x.finished_at.map(utc),
+                pre_hook_started_at: x.pre_hook_started_at.map(utc),
+                pre_hook_finished_at: x.pre_hook_finished_at.map(utc),
+                post_hook_started_at: x.post_hook_started_at.map(utc),
+                post_hook_finished_at: x.post_hook_finished_at.map(utc),
+                clock_skew: x.clock_skew,
+                received_at: x.received_at.map(utc),
+                pre_hook_duration: x.pre_hook_duration,
+                post_hook_duration: x.post_hook_duration,
+                create_duration: x.create_duration,
+                complete_duration: x.complete_duration,
+                nfiles: x.nfiles,
+                original_size: x.original_size,
+                compressed_size: x.compressed_size,
+                deduplicated_size: x.deduplicated_size,
+                created_at: DateTime::from_utc(x.created_at, Utc),
+            })
+            .collect(),
+        next_cursor,
+    }))
+}
+
+/// The size of the buckets the stats are aggregated in
+#[derive(Deserialize, ToSchema, Copy, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsBucketSize {
+    /// Aggregate the stats per hour
+    Hour,
+    /// Aggregate the stats per day
+    Day,
+    /// Aggregate the stats per week, starting on monday
+    Week,
+}
+
+impl StatsBucketSize {
+    /// Retrieve the start of the bucket the given point in time belongs to
+    fn bucket_start(&self, time: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            StatsBucketSize::Hour => start_of_hour(time),
+            StatsBucketSize::Day => start_of_day(time),
+            StatsBucketSize::Week => start_of_week(time),
+        }
+    }
+}
+
+/// The query parameters to retrieve the aggregated stats of a drone
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetAggregatedDroneStatsQuery {
+    /// The size of the buckets
+    bucket: StatsBucketSize,
+    /// Only include stats collected at or after this point in time
+    from: Option<DateTime<Utc>>,
+    /// Only include stats collected before this point in time
+    to: Option<DateTime<Utc>>,
+    /// The maximum number of buckets to return.
+    ///
+    /// Defaults to 1000, can be at most 10000.
+    limit: Option<u64>,
+    /// Continue at this point in time.
+    ///
+    /// Use the `next_cursor` of the previous response.
+    cursor: Option<DateTime<Utc>>,
+}
+
+/// The minimum, average and maximum of a value in a bucket
+#[derive(Serialize, ToSchema)]
+pub struct AggregatedValue {
+    min: i64,
+    avg: f64,
+    max: i64,
+}
+
+/// The aggregated stats of a drone in a single bucket
+#[derive(Serialize, ToSchema)]
+pub struct DroneStatBucket {
+    /// The start of the bucket
+    start: DateTime<Utc>,
+    /// The number of stat records in this bucket
+    count: i64,
+    pre_hook_duration: Option<AggregatedValue>,
+    post_hook_duration: Option<AggregatedValue>,
+    create_duration: AggregatedValue,
+    complete_duration: AggregatedValue,
+    original_size: AggregatedValue,
+    compressed_size: AggregatedValue,
+    deduplicated_size: AggregatedValue,
+    nfiles: AggregatedValue,
+}
+
+/// The aggregated stats of a drone
+///
+/// If there are more buckets available, `next_cursor` is set.
+#[derive(Serialize, ToSchema)]
+pub struct GetAggregatedDroneStats {
+    buckets: Vec<DroneStatBucket>,
+    next_cursor: Option<DateTime<Utc>>,
+}
+
+/// Retrieve the stats of a drone aggregated in buckets
+///
+/// For every bucket, the minimum, average and maximum of all durations and sizes is calculated.
+/// Empty buckets are omitted.
+///
+/// Stats that were already aggregated by the retention job are weighted by the number of records
+/// they represent and are put in the bucket their period starts in.
+/// Their minimums and maximums are used, unless they were aggregated before these were recorded.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the aggregated stats of the drone", body = GetAggregatedDroneStats),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid, GetAggregatedDroneStatsQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/stats/aggregated")]
+pub async fn get_aggregated_drone_stats(
+    path: Path<PathUuid>,
+    query: Query<GetAggregatedDroneStatsQuery>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetAggregatedDroneStats>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let limit = get_limit(query.limit, DEFAULT_STATS_LIMIT, MAX_STATS_LIMIT)?;
+
+    let from = start_of_range(query.from, query.cursor);
+    let to = query.to.map(|x| x.naive_utc());
+
+    let mut aggregates = query!(&mut tx, DroneStatsAggregate)
+        .condition(aggregate_condition(&drone, from, to, None))
+        .order_asc(DroneStatsAggregate::F.created_at)
+        .all()
+        .await?
+        .into_iter()
+        .map(StatsSample::from)
+        .peekable();
+
+    let mut collector = BucketCollector::new(query.bucket, limit);
+
+    {
+        let mut stream = query!(&mut tx, DroneStatsSample)
+            .condition(stats_condition(&drone, from, to, None))
+            .order_asc(DroneStats::F.collected_at)
+            .stream();
+
+        'outer: while let Some(stat) = stream.next().await {
+            let stat = StatsSample::from(stat?);
+
+            while let Some(aggregate) = aggregates.next_if(|x| x.created_at <= stat.created_at) {
+                if !collector.push(aggregate) {
+                    break 'outer;
+                }
+            }
+
+            if !collector.push(stat) {
+                break;
+            }
+        }
+    }
+
+    for aggregate in aggregates {
+        if !collector.push(aggregate) {
+            break;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(Json(collector.finish()))
+}
+
+/// Validate the requested limit, using `default` if none was requested
+fn get_limit(limit: Option<u64>, default: u64, max: u64) -> ApiResult<u64> {
+    match limit {
+        None => Ok(default),
+        Some(limit) if limit == 0 || limit > max => Err(ApiError::InvalidLimit),
+        Some(limit) => Ok(limit),
+    }
+}
+
+/// Get the start of the requested range, taking the cursor into account
+fn start_of_range(
+    from: Option<DateTime<Utc>>,
+    cursor: Option<DateTime<Utc>>,
+) -> Option<NaiveDateTime> {
+    from.max(cursor).map(|x| x.naive_utc())
+}
+
+/// Build the condition to select the stats of a drone in the given range after the cursor
+fn stats_condition<'a>(
+    drone: &'a Uuid,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+    cursor: Option<&'a StatsCursor>,
+) -> DynamicCollection<BoxedCondition<'a>> {
+    // Stats are ordered by the point in time they were collected,
+    // which is set for all stats once they were backfilled on startup
+    let mut conditions = vec![
+        DroneStats::F.drone.equals(drone.as_ref()).boxed(),
+        DroneStats::F.collected_at.is_not_null().boxed(),
+    ];
+    if let Some(from) = from {
+        conditions.push(DroneStats::F.collected_at.greater_or_equals(from).boxed());
+    }
+    if let Some(to) = to {
+        conditions.push(DroneStats::F.collected_at.less(to).boxed());
+    }
+    if let Some(cursor) = cursor {
+        conditions.push(
+            DynamicCollection::or(vec![
+                DroneStats::F
+                    .collected_at
+                    .greater(cursor.created_at)
+                    .boxed(),
+                DynamicCollection::and(vec![
+                    DroneStats::F.collected_at.equals(cursor.created_at).boxed(),
+                    DroneStats::F.uuid.greater(cursor.uuid.as_ref()).boxed(),
+                ])
+                .boxed(),
+            ])
+            .boxed(),
+        );
+    }
+
+    DynamicCollection::and(conditions)
+}
+
+/// Build the condition to select the aggregated stats of a drone in the given range after the cursor
+fn aggregate_condition<'a>(
+    drone: &'a Uuid,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+    cursor: Option<&'a StatsCursor>,
+) -> DynamicCollection<BoxedCondition<'a>> {
+    let mut conditions = vec![DroneStatsAggregate::F.drone.equals(drone.as_ref()).boxed()];
+    if let Some(from) = from {
+        conditions.push(
+            DroneStatsAggregate::F
+                .created_at
+                .greater_or_equals(from)
+                .boxed(),
+        );
+    }
+    if let Some(to) = to {
+        conditions.push(DroneStatsAggregate::F.created_at.less(to).boxed());
+    }
+    if let Some(cursor) = cursor {
+        conditions.push(
+            DynamicCollection::or(vec![
+                DroneStatsAggregate::F
+                    .created_at
+                    .greater(cursor.created_at)
+                    .boxed(),
+                DynamicCollection::and(vec![
+                    DroneStatsAggregate::F
+                        .created_at
+                        .equals(cursor.created_at)
+                        .boxed(),
+                    DroneStatsAggregate::F
+                        .uuid
+                        .greater(cursor.uuid.as_ref())
+                        .boxed(),
+                ])
+                .boxed(),
+            ])
+            .boxed(),
+        );
+    }
+
+    DynamicCollection::and(conditions)
+}
+
+impl From<ValueAccumulator> for AggregatedValue {
+    fn from(value: ValueAccumulator) -> Self {
+        Self {
+            min: value.min,
+            avg: value.avg(),
+            max: value.max,
+        }
+    }
+}
+
+/// Collects stats samples into a limited number of buckets
+struct BucketCollector {
+    size: StatsBucketSize,
+    limit: u64,
+    buckets: Vec<(NaiveDateTime, StatsAccumulator)>,
+    next_cursor: Option<NaiveDateTime>,
+}
+
+impl BucketCollector {
+    fn new(size: StatsBucketSize, limit: u64) -> Self {
+        Self {
+            size,
+            limit,
+            buckets: vec![],
+            next_cursor: None,
+        }
+    }
+
+    /// Add a sample to its bucket
+    ///
+    /// Returns `false` if the limit of buckets is reached and no further samples are accepted.
+    fn push(&mut self, sample: StatsSample) -> bool {
+        if self.next_cursor.is_some() {
+            return false;
+        }
+
+        let start = self.size.bucket_start(sample.created_at);
+        match self.buckets.last_mut() {
+            Some((bucket_start, bucket)) if *bucket_start == start => bucket.add(&sample),
+            _ => {
+                if self.buckets.len() as u64 == self.limit {
+                    self.next_cursor = Some(start);
+                    return false;
+                }
+
+                self.buckets.push((start, StatsAccumulator::new(&sample)));
+            }
+        }
+
+        true
+    }
+
+    fn finish(self) -> GetAggregatedDroneStats {
+        GetAggregatedDroneStats {
+            buckets: self
+                .buckets
+                .into_iter()
+                .map(|(start, bucket)| DroneStatBucket {
+                    start: DateTime::from_utc(start, Utc),
+                    count: bucket.count,
+                    pre_hook_duration: bucket.pre_hook_duration.map(AggregatedValue::from),
+                    post_hook_duration: bucket.post_hook_duration.map(AggregatedValue::from),
+                    create_duration: bucket.create_duration.into(),
+                    complete_duration: bucket.complete_duration.into(),
+                    original_size: bucket.original_size.into(),
+                    compressed_size: bucket.compressed_size.into(),
+                    deduplicated_size: bucket.deduplicated_size.into(),
+                    nfiles: bucket.nfiles.into(),
+                })
+                .collect(),
+            next_cursor: self.next_cursor.map(|x| DateTime::from_utc(x, Utc)),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use actix_web::get;
+use actix_web::web::{Data, Json, Path, Query};
+use chrono::{DateTime, Utc};
+use rorm::conditions::{BoxedCondition, Condition, DynamicCollection};
+use rorm::{query, Database, Model};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::handler::{ApiError, ApiResult, PathUuid};
+use crate::models::{Drone, DroneWarning};
+
+/// The query parameters to retrieve the borg warnings of a drone
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetWarningsQuery {
+    /// Only include warnings reported at or after this point in time
+    from: Option<DateTime<Utc>>,
+    /// Only include warnings reported before this point in time
+    to: Option<DateTime<Utc>>,
+}
+
+/// A warning borg logged while the drone created an archive
+#[derive(Serialize, ToSchema)]
+pub struct DroneWarningResponse {
+    uuid: Uuid,
+    /// The stats of the archive creation the warning was logged in
+    ///
+    /// Not set once the stats were aggregated.
+    stats: Option<Uuid>,
+    #[schema(example = "/var/log/syslog: file changed while we backed it up")]
+    message: String,
+    /// The path the warning is about
+    #[schema(example = "/var/log/syslog")]
+    path: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+/// The borg warnings of a drone
+#[derive(Serialize, ToSchema)]
+pub struct GetWarningsResponse {
+    warnings: Vec<DroneWarningResponse>,
+}
+
+/// Retrieve the warnings borg logged while a drone created archives
+///
+/// At most 1000 warnings are stored per archive creation.
+/// Warnings are removed together with their stats by the retention.
+/// The warnings are ordered by the point in time they were reported, starting with the oldest.
+#[utoipa::path(
+    tag = "Drone management",
+    context_path = "/api/frontend/v1",
+    responses(
+        (status = 200, description = "Retrieved the warnings of the drone", body = GetWarningsResponse),
+        (status = 400, description = "Client error", body = ApiErrorResponse),
+        (status = 500, description = "Server error", body = ApiErrorResponse)
+    ),
+    params(PathUuid, GetWarningsQuery),
+    security(("session_cookie" = [])),
+)]
+#[get("/drones/{uuid}/warnings")]
+pub async fn get_warnings(
+    path: Path<PathUuid>,
+    query: Query<GetWarningsQuery>,
+    db: Data<Database>,
+) -> ApiResult<Json<GetWarningsResponse>> {
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, (Drone::F.uuid,))
+        .condition(Drone::F.uuid.equals(path.uuid.as_ref()))
+        .optional()
+        .await?
+        .ok_or(ApiError::InvalidUuid)?
+        .0;
+
+    let mut conditions: Vec<BoxedCondition<'_>> =
+        vec![DroneWarning::F.drone.equals(drone.as_ref()).boxed()];
+    if let Some(from) = query.from {
+        conditions.push(
+            DroneWarning::F
+                .created_at
+                .greater_or_equals(from.naive_utc())
+                .boxed(),
+        );
+    }
+    if let Some(to) = query.to {
+        conditions.push(DroneWarning::F.created_at.less(to.naive_utc()).boxed());
+    }
+
+    let warnings = query!(&mut tx, DroneWarning)
+        .condition(DynamicCollection::and(conditions))
+        .order_asc(DroneWarning::F.created_at)
+        .all()
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(GetWarningsResponse {
+        warnings: warnings
+            .into_iter()
+            .map(|x| DroneWarningResponse {
+                uuid: x.uuid,
+                stats: x.stats.map(|x| *x.key()),
+                message: x.message,
+                path: x.path,
+                created_at: DateTime::from_utc(x.created_at, Utc),
+            })
+            .collect(),
+    }))
+}
@@ -0,0 +1,247 @@
+//! The prometheus metrics of the vinculum are exposed in this module
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+
+use actix_web::web::Data;
+use actix_web::{get, HttpRequest, HttpResponse};
+use chrono::{Duration, NaiveDateTime, Utc};
+use futures::TryStreamExt;
+use rorm::{query, Database, Model};
+use subtle::ConstantTimeEq;
+
+use crate::chan::MatrixNotifierChan;
+use crate::config::Config;
+use crate::handler::{bearer_token, ApiError, ApiResult};
+use crate::models::{Drone, DroneStats, DroneStatsSample};
+
+/// A single metric family in the prometheus text format
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+    kind: &'static str,
+    samples: Vec<(String, f64)>,
+}
+
+impl Metric {
+    fn gauge(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            kind: "gauge",
+            samples: vec![],
+        }
+    }
+
+    fn counter(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            kind: "counter",
+            samples: vec![],
+        }
+    }
+
+    /// Add a sample with the labels of a drone
+    fn drone(&mut self, drone: &Drone, value: f64) {
+        self.samples.push((
+            format!(
+                r#"{{drone="{name}",uuid="{uuid}"}}"#,
+                name = escape_label(&drone.name),
+                uuid = drone.uuid
+            ),
+            value,
+        ));
+    }
+
+    /// Add a sample without labels
+    fn value(&mut self, value: f64) {
+        self.samples.push((String::new(), value));
+    }
+
+    fn write(&self, out: &mut String) {
+        // Writing to a string can't fail
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} {}", self.name, self.kind);
+        for (labels, value) in &self.samples {
+            let _ = writeln!(out, "{}{labels} {value}", self.name);
+        }
+    }
+}
+
+/// Escape a label value for the prometheus text format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+fn timestamp(time: NaiveDateTime) -> f64 {
+    time.timestamp() as f64
+}
+
+/// Expose metrics about the drones and the vinculum in the prometheus text format
+///
+/// Prometheus has to provide the configured scrape token as bearer token.
+/// If no metrics are configured, the endpoint is not available.
+#[get("/metrics")]
+pub async fn metrics(
+    raw_req: HttpRequest,
+    db: Data<Database>,
+    config: Data<Config>,
+    matrix: Data<MatrixNotifierChan>,
+) -> ApiResult<HttpResponse> {
+    let metrics_config = config.metrics.as_ref().ok_or(ApiError::NotFound)?;
+    let token = bearer_token(&raw_req)?;
+    if !bool::from(
+        token
+            .as_bytes()
+            .ct_eq(metrics_config.scrape_token.as_bytes()),
+    ) {
+        return Err(ApiError::Unauthenticated);
+    }
+
+    let mut tx = db.start_transaction().await?;
+
+    let drones = query!(&mut tx, Drone)
+        .condition(Drone::F.archived_at.is_null())
+        .all()
+        .await?;
+
+    // The latest stats of every drone.
+    // Stats without a point in time of collection have not been backfilled yet.
+    let mut latest_stats = HashMap::new();
+    let mut stats = query!(&mut tx, DroneStatsSample)
+        .condition(DroneStats::F.collected_at.is_not_null())
+        .order_desc(DroneStats::F.collected_at)
+        .stream();
+    while let Some(x) = stats.try_next().await? {
+        latest_stats.entry(*x.drone.key()).or_insert(x);
+    }
+    drop(stats);
+
+    let now = Utc::now().naive_utc();
+    let overdue_after = Duration::hours(metrics_config.overdue_after_hours as i64);
+
+    let mut active = Metric::gauge("borg_drone_active", "Whether the drone is active");
+    let mut overdue = Metric::gauge(
+        "borg_drone_overdue",
+        "Whether the drone is active and has not reported successfully in time",
+    );
+    let mut last_success = Metric::gauge(
+        "borg_drone_last_success_timestamp_seconds",
+        "The point in time of the last successful report of the drone",
+    );
+    let mut last_failure = Metric::gauge(
+        "borg_drone_last_failure_timestamp_seconds",
+        "The point in time of the last error report of the drone",
+    );
+    let mut pre_hook_duration = Metric::gauge(
+        "borg_drone_last_pre_hook_duration_seconds",
+        "The duration of the pre hook of the last successful run",
+    );
+    let mut post_hook_duration = Metric::gauge(
+        "borg_drone_last_post_hook_duration_seconds",
+        "The duration of the post hook of the last successful run",
+    );
+    let mut create_duration = Metric::gauge(
+        "borg_drone_last_create_duration_seconds",
+        "The duration of the archive creation of the last successful run",
+    );
+    let mut complete_duration = Metric::gauge(
+        "borg_drone_last_complete_duration_seconds",
+        "The duration of the last successful run",
+    );
+    let mut original_size = Metric::gauge(
+        "borg_drone_last_original_size_bytes",
+        "The original size of the last archive",
+    );
+    let mut compressed_size = Metric::gauge(
+        "borg_drone_last_compressed_size_bytes",
+        "The compressed size of the last archive",
+    );
+    let mut deduplicated_size = Metric::gauge(
+        "borg_drone_last_deduplicated_size_bytes",
+        "The deduplicated size of the last archive",
+    );
+    let mut nfiles = Metric::gauge(
+        "borg_drone_last_files",
+        "The number of files in the last archive",
+    );
+
+    for drone in &drones {
+        let is_overdue = drone.active
+            && drone
+                .last_activity
+                .map_or(true, |x| now - x > overdue_after);
+
+        active.drone(drone, drone.active as u8 as f64);
+        overdue.drone(drone, is_overdue as u8 as f64);
+        if let Some(x) = drone.last_activity {
+            last_success.drone(drone, timestamp(x));
+        }
+        if let Some(x) = drone.last_error {
+            last_failure.drone(drone, timestamp(x));
+        }
+
+        if let Some(stats) = latest_stats.get(&drone.uuid) {
+            if let Some(x) = stats.pre_hook_duration {
+                pre_hook_duration.drone(drone, x as f64);
+            }
+            if let Some(x) = stats.post_hook_duration {
+                post_hook_duration.drone(drone, x as f64);
+            }
+            create_duration.drone(drone, stats.create_duration as f64);
+            complete_duration.drone(drone, stats.complete_duration as f64);
+            original_size.drone(drone, stats.original_size as f64);
+            compressed_size.drone(drone, stats.compressed_size as f64);
+            deduplicated_size.drone(drone, stats.deduplicated_size as f64);
+            nfiles.drone(drone, stats.nfiles as f64);
+        }
+    }
+
+    tx.commit().await?;
+
+    let mut queue_depth = Metric::gauge(
+        "vinculum_notifier_queue_depth",
+        "The number of notifications waiting to be sent",
+    );
+    queue_depth.value(matrix.queue_depth() as f64);
+    let mut sent = Metric::counter(
+        "vinculum_notifier_sent_total",
+        "The number of notifications that were sent",
+    );
+    sent.value(matrix.stats().sent.load(Ordering::Relaxed) as f64);
+    let mut failed = Metric::counter(
+        "vinculum_notifier_failed_total",
+        "The number of notifications that could not be sent",
+    );
+    failed.value(matrix.stats().failed.load(Ordering::Relaxed) as f64);
+
+    let mut out = String::new();
+    for metric in [
+        active,
+        overdue,
+        last_success,
+        last_failure,
+        pre_hook_duration,
+        post_hook_duration,
+        create_duration,
+        complete_duration,
+        original_size,
+        compressed_size,
+        deduplicated_size,
+        nfiles,
+        queue_depth,
+        sent,
+        failed,
+    ] {
+        metric.write(&mut out);
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(out))
+}
@@ -4,16 +4,18 @@ use std::fmt::{Display, Formatter};
 
 use actix_toolbox::tb_middleware::actix_session;
 use actix_web::body::BoxBody;
-use actix_web::HttpResponse;
+use actix_web::{HttpRequest, HttpResponse};
 use borgbackup::errors::ListError;
+use common::borg::BorgError;
 use log::{debug, error, info, trace, warn};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_repr::Serialize_repr;
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 pub mod api;
 pub mod frontend;
+pub mod metrics;
 
 /// The path parameter for an uuid
 #[derive(Deserialize, IntoParams)]
@@ -21,6 +23,42 @@ pub struct PathUuid {
     uuid: Uuid,
 }
 
+/// Retrieve the bearer token from the `Authorization` header of a request
+pub(crate) fn bearer_token(raw_req: &HttpRequest) -> ApiResult<&str> {
+    let auth_header = raw_req
+        .headers()
+        .get("Authorization")
+        .ok_or(ApiError::Unauthenticated)?;
+
+    let auth_value = auth_header.to_str().map_err(|e| {
+        debug!("Invalid characters in header: {e}");
+        ApiError::Unauthenticated
+    })?;
+
+    let h: Vec<&str> = auth_value.split(' ').collect();
+    if h.len() != 2 {
+        return Err(ApiError::Unauthenticated);
+    }
+
+    if *h.first().unwrap() != "Bearer" {
+        return Err(ApiError::Unauthenticated);
+    }
+
+    Ok(*h.get(1).unwrap())
+}
+
+/// Deserialize a present value into `Some`
+///
+/// Combined with `#[serde(default)]` on an `Option<Option<T>>`, this differentiates between
+/// a missing value (`None`) and an explicit `null` (`Some(None)`).
+pub(crate) fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
 /// The result that is used throughout the complete api.
 pub type ApiResult<T> = Result<T, ApiError>;
 
@@ -45,6 +83,18 @@ pub(crate) enum ApiStatusCode {
     ListRepositoryError = 1009,
     RepositoryAlreadyExists = 1010,
     InvalidUuid = 1011,
+    DroneArchived = 1012,
+    InvalidLimit = 1013,
+    InvalidCapacityLimit = 1014,
+    BorgError = 1015,
+    RestoreForbidden = 1016,
+    InvalidArchive = 1017,
+    InvalidPath = 1018,
+    InvalidCheckPolicy = 1019,
+    InvalidPrunePolicy = 1020,
+    NameOfArchivedDrone = 1021,
+    RepositoryOfArchivedDrone = 1022,
+    InvalidCursor = 1023,
 
     InternalServerError = 2000,
     DatabaseError = 2001,
@@ -100,6 +150,30 @@ pub enum ApiError {
     RepositoryAlreadyExists,
     /// An invalid uuid was specified
     InvalidUuid,
+    /// The drone is archived
+    DroneArchived,
+    /// An invalid limit was specified
+    InvalidLimit,
+    /// An invalid capacity limit was specified
+    InvalidCapacityLimit,
+    /// Error while executing borg
+    BorgError(BorgError),
+    /// The account is not allowed to access the contents of archives
+    RestoreForbidden,
+    /// An invalid archive name was specified
+    InvalidArchive,
+    /// An invalid path was specified
+    InvalidPath,
+    /// An invalid check policy was specified
+    InvalidCheckPolicy,
+    /// An invalid prune policy was specified
+    InvalidPrunePolicy(String),
+    /// The name is used by an archived drone
+    NameOfArchivedDrone,
+    /// The repository is used by an archived drone
+    RepositoryOfArchivedDrone,
+    /// An invalid cursor was specified
+    InvalidCursor,
 
     /// Unknown error occurred
     InternalServerError,
@@ -144,6 +218,29 @@ impl Display for ApiError {
                 write!(f, "There exists already an entity with that repository")
             }
             ApiError::InvalidUuid => write!(f, "Invalid uuid specified"),
+            ApiError::DroneArchived => write!(f, "The drone is archived"),
+            ApiError::InvalidLimit => write!(f, "Invalid limit specified"),
+            ApiError::InvalidCapacityLimit => write!(f, "Invalid capacity limit specified"),
+            ApiError::BorgError(err) => write!(f, "Error while executing borg: {err}"),
+            ApiError::RestoreForbidden => {
+                write!(
+                    f,
+                    "The account is not allowed to access the contents of archives"
+                )
+            }
+            ApiError::InvalidArchive => write!(f, "Invalid archive specified"),
+            ApiError::InvalidPath => write!(f, "Invalid path specified"),
+            ApiError::InvalidCheckPolicy => write!(f, "Invalid check policy specified"),
+            ApiError::InvalidPrunePolicy(err) => write!(f, "Invalid prune policy specified: {err}"),
+            ApiError::NameOfArchivedDrone => write!(
+                f,
+                "The name is used by an archived drone, rename or purge the archived drone to use it"
+            ),
+            ApiError::RepositoryOfArchivedDrone => write!(
+                f,
+                "The repository is used by an archived drone, purge the archived drone to use it"
+            ),
+            ApiError::InvalidCursor => write!(f, "Invalid cursor specified"),
         }
     }
 }
@@ -278,6 +375,59 @@ impl actix_web::ResponseError for ApiError {
                 ApiStatusCode::InvalidUuid,
                 self.to_string(),
             )),
+            ApiError::DroneArchived => {
+                debug!("Drone is archived");
+                HttpResponse::BadRequest().json(ApiErrorResponse::new(
+                    ApiStatusCode::DroneArchived,
+                    self.to_string(),
+                ))
+            }
+            ApiError::InvalidLimit => HttpResponse::BadRequest().json(ApiErrorResponse::new(
+                ApiStatusCode::InvalidLimit,
+                self.to_string(),
+            )),
+            ApiError::InvalidCapacityLimit => HttpResponse::BadRequest().json(
+                ApiErrorResponse::new(ApiStatusCode::InvalidCapacityLimit, self.to_string()),
+            ),
+            ApiError::BorgError(err) => {
+                info!("Error while executing borg: {err}");
+                HttpResponse::BadRequest().json(ApiErrorResponse::new(
+                    ApiStatusCode::BorgError,
+                    self.to_string(),
+                ))
+            }
+            ApiError::RestoreForbidden => {
+                warn!("Account tried to access the contents of an archive without permission");
+                HttpResponse::BadRequest().json(ApiErrorResponse::new(
+                    ApiStatusCode::RestoreForbidden,
+                    self.to_string(),
+                ))
+            }
+            ApiError::InvalidArchive => HttpResponse::BadRequest().json(ApiErrorResponse::new(
+                ApiStatusCode::InvalidArchive,
+                self.to_string(),
+            )),
+            ApiError::InvalidPath => HttpResponse::BadRequest().json(ApiErrorResponse::new(
+                ApiStatusCode::InvalidPath,
+                self.to_string(),
+            )),
+            ApiError::InvalidCheckPolicy => HttpResponse::BadRequest().json(ApiErrorResponse::new(
+                ApiStatusCode::InvalidCheckPolicy,
+                self.to_string(),
+            )),
+            ApiError::InvalidPrunePolicy(_) => HttpResponse::BadRequest().json(
+                ApiErrorResponse::new(ApiStatusCode::InvalidPrunePolicy, self.to_string()),
+            ),
+            ApiError::NameOfArchivedDrone => HttpResponse::BadRequest().json(
+                ApiErrorResponse::new(ApiStatusCode::NameOfArchivedDrone, self.to_string()),
+            ),
+            ApiError::RepositoryOfArchivedDrone => HttpResponse::BadRequest().json(
+                ApiErrorResponse::new(ApiStatusCode::RepositoryOfArchivedDrone, self.to_string()),
+            ),
+            ApiError::InvalidCursor => HttpResponse::BadRequest().json(ApiErrorResponse::new(
+                ApiStatusCode::InvalidCursor,
+                self.to_string(),
+            )),
         }
     }
 }
@@ -306,6 +456,12 @@ impl From<actix_session::SessionGetError> for ApiError {
     }
 }
 
+impl From<BorgError> for ApiError {
+    fn from(value: BorgError) -> Self {
+        Self::BorgError(value)
+    }
+}
+
 impl From<ListError> for ApiError {
     fn from(value: ListError) -> Self {
         Self::ListRepositoryError(value)
@@ -25,7 +25,13 @@ use uuid::Uuid;
 use crate::chan::start_matrix_notifier;
 use crate::config::Config;
 use crate::models::{Account, AccountInsert};
+use crate::modules::borg::common_options;
 use crate::modules::matrix::MatrixApi;
+use crate::tasks::check::start_check_task;
+use crate::tasks::drill::start_drill_task;
+use crate::tasks::forecast::start_forecast_task;
+use crate::tasks::repository::start_repository_task;
+use crate::tasks::retention::{backfill_collected_at, start_retention_task};
 
 pub(crate) mod chan;
 pub mod config;
@@ -35,6 +41,7 @@ pub mod models;
 pub mod modules;
 pub mod server;
 pub(crate) mod swagger;
+pub(crate) mod tasks;
 
 /// The subcommands of the vinculum
 #[derive(Subcommand)]
@@ -78,9 +85,34 @@ async fn main() -> Result<(), String> {
 
             let db = get_db(&conf).await?;
 
+            backfill_collected_at(&db).await.map_err(|e| {
+                format!("Could not set the point in time of collection of stats: {e}")
+            })?;
+
             let matrix = MatrixApi::new(conf.matrix.homeserver.clone().parse().unwrap());
             let matrix_notifier_chan = start_matrix_notifier(&conf, matrix).await?;
 
+            start_retention_task(&conf, db.clone());
+            start_repository_task(&conf, db.clone(), common_options(&conf.borg));
+            start_forecast_task(
+                &conf,
+                db.clone(),
+                common_options(&conf.borg),
+                matrix_notifier_chan.clone(),
+            );
+
+            start_check_task(
+                db.clone(),
+                common_options(&conf.borg),
+                matrix_notifier_chan.clone(),
+            );
+            start_drill_task(
+                &conf,
+                db.clone(),
+                common_options(&conf.borg),
+                matrix_notifier_chan.clone(),
+            );
+
             server::start_server(&conf, db, matrix_notifier_chan).await?;
         }
         Command::Keygen => {
@@ -0,0 +1,105 @@
+use rorm::fields::ForeignModel;
+use rorm::{DbEnum, Model, Patch};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::Drone;
+
+/// The extent of a `borg check`
+#[derive(DbEnum, Deserialize, Serialize, ToSchema, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CheckMode {
+    /// Only check the consistency of the repository (`--repository-only`)
+    ///
+    /// This is the only mode that supports partial checks.
+    RepositoryOnly,
+    /// Check the consistency of the repository and the archives
+    Full,
+    /// Check the consistency of the repository and the archives and verify the integrity
+    /// of the data by reading it (`--verify-data`)
+    VerifyData,
+}
+
+impl From<CheckMode> for common::borg::CheckMode {
+    fn from(value: CheckMode) -> Self {
+        match value {
+            CheckMode::RepositoryOnly => Self::RepositoryOnly,
+            CheckMode::Full => Self::Full,
+            CheckMode::VerifyData => Self::VerifyData,
+        }
+    }
+}
+
+/// The policy of the scheduled integrity checks of the repository of a drone
+///
+/// The repository is locked while it is checked.
+#[derive(Model)]
+pub struct DroneCheckPolicy {
+    /// The primary key of the policy
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The drone whose repository is checked
+    #[rorm(on_update = "Cascade", on_delete = "Cascade", unique)]
+    pub drone: ForeignModel<Drone>,
+
+    /// The extent of the check
+    pub mode: CheckMode,
+    /// The interval in hours the repository is checked in
+    pub interval_hours: i64,
+    /// The maximum duration in seconds of a partial check (`--max-duration`)
+    ///
+    /// Only supported by [CheckMode::RepositoryOnly].
+    pub max_duration: Option<i64>,
+}
+
+#[derive(Patch)]
+#[rorm(model = "DroneCheckPolicy")]
+pub(crate) struct DroneCheckPolicyInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) mode: CheckMode,
+    pub(crate) interval_hours: i64,
+    pub(crate) max_duration: Option<i64>,
+}
+
+/// The result of a `borg check` of the repository of a drone
+#[derive(Model)]
+pub struct DroneCheck {
+    /// The primary key of the check
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The drone whose repository was checked
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub drone: ForeignModel<Drone>,
+
+    /// The extent of the check
+    pub mode: CheckMode,
+    /// Whether the check was partial, as it was limited by a maximum duration
+    pub partial: bool,
+    /// Whether borg found no problems
+    pub success: bool,
+    /// The duration in seconds the check took
+    pub duration: i64,
+
+    /// The problems borg found, or the error that occurred while executing borg
+    #[rorm(max_length = 65535)]
+    pub output: Option<String>,
+
+    /// The point in time the check finished
+    #[rorm(auto_create_time)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Patch)]
+#[rorm(model = "DroneCheck")]
+pub(crate) struct DroneCheckInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) mode: CheckMode,
+    pub(crate) partial: bool,
+    pub(crate) success: bool,
+    pub(crate) duration: i64,
+    pub(crate) output: Option<String>,
+}
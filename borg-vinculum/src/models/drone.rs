@@ -1,5 +1,7 @@
 use rorm::fields::{BackRef, ForeignModel};
-use rorm::{field, Model, Patch};
+use rorm::{field, DbEnum, Model, Patch};
+use serde::Serialize;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 /// The model representing a borg drone instance
@@ -13,10 +15,19 @@ pub struct Drone {
     #[rorm(max_length = 255, unique)]
     pub name: String,
 
-    /// The state of the drone
+    /// Whether the drone is active
+    ///
+    /// A drone gets activated on its successful reports, unless it is `deactivated`.
+    /// Errors of inactive drones are not forwarded to the notifier.
     #[rorm(default = false)]
     pub active: bool,
 
+    /// Whether the drone was deactivated explicitly
+    ///
+    /// Deactivated drones are not activated by their reports anymore.
+    #[rorm(default = false)]
+    pub deactivated: bool,
+
     /// The token of the drone
     #[rorm(max_length = 255)]
     pub token: String,
@@ -36,8 +47,46 @@ pub struct Drone {
     /// The stats of this drone
     pub stats: BackRef<field!(DroneStats::F.drone)>,
 
-    /// The last time the drone has contacted the vinculum
+    /// The last time the drone reported a successful backup
     pub last_activity: Option<chrono::NaiveDateTime>,
+
+    /// The last time the drone has reported an error
+    pub last_error: Option<chrono::NaiveDateTime>,
+
+    /// The capacity limit of the repository in bytes
+    ///
+    /// It is used to forecast when the repository will be full.
+    pub capacity_limit: Option<i64>,
+
+    /// Whether the repository is forecast to reach its capacity limit soon
+    ///
+    /// Only changes of this state are notified.
+    #[rorm(default = false)]
+    pub capacity_alert: bool,
+
+    /// The point in time the drone was archived
+    ///
+    /// Archived drones are hidden, can't authenticate anymore, but keep their stats.
+    pub archived_at: Option<chrono::NaiveDateTime>,
+
+    /// The hostname of the host the drone runs on, as last reported
+    #[rorm(max_length = 255)]
+    pub hostname: Option<String>,
+    /// The operating system of the host, as last reported
+    #[rorm(max_length = 255)]
+    pub os: Option<String>,
+    /// The kernel release of the host, as last reported
+    #[rorm(max_length = 255)]
+    pub kernel: Option<String>,
+    /// The version of borg on the host, as last reported
+    #[rorm(max_length = 255)]
+    pub borg_version: Option<String>,
+    /// The version of borg-drone, as last reported
+    #[rorm(max_length = 255)]
+    pub drone_version: Option<String>,
+    /// The compression the drone creates archives with, as last reported
+    #[rorm(max_length = 255)]
+    pub compression: Option<String>,
 }
 
 #[derive(Patch)]
@@ -78,7 +127,80 @@ pub struct DroneStats {
     /// Number of archived files
     pub nfiles: i64,
 
+    /// Whether the stats deviated from the baseline of the previous stats
+    #[rorm(default = false)]
+    pub anomaly: bool,
+
+    /// The number of warnings borg logged while creating the archive
+    #[rorm(default = 0)]
+    pub warnings: i64,
+
+    /// The number of added files, not set if the drone didn't report it
+    pub files_added: Option<i64>,
+    /// The number of modified files, not set if the drone didn't report it
+    pub files_modified: Option<i64>,
+    /// The number of unchanged files, not set if the drone didn't report it
+    pub files_unchanged: Option<i64>,
+    /// The added and modified paths as json list of [common::ChangedPath]
+    ///
+    /// Only set if the drone reports the paths.
+    #[rorm(max_length = 262144)]
+    pub changed_paths: Option<String>,
+    /// Whether `changed_paths` doesn't contain all changed paths
+    #[rorm(default = false)]
+    pub changed_paths_truncated: bool,
+
+    /// The name of the created archive, not set if the drone didn't report it
+    #[rorm(max_length = 255)]
+    pub archive_name: Option<String>,
+    /// The hexadecimal id of the created archive, not set if the drone didn't report it
+    #[rorm(max_length = 64)]
+    pub archive_id: Option<String>,
+    /// The point in time borg started to create the archive, in the local time of the drone
+    pub archive_start: Option<chrono::NaiveDateTime>,
+    /// The point in time borg finished the archive, in the local time of the drone
+    pub archive_end: Option<chrono::NaiveDateTime>,
+
+    /// The number of chunks in the repository after the archive was created
+    pub repository_total_chunks: Option<i64>,
+    /// The compressed size of all chunks multiplied with their reference counts in bytes
+    pub repository_total_csize: Option<i64>,
+    /// The uncompressed size of all chunks multiplied with their reference counts in bytes
+    pub repository_total_size: Option<i64>,
+    /// The number of unique chunks in the repository
+    pub repository_total_unique_chunks: Option<i64>,
+    /// The compressed size of all unique chunks in bytes
+    pub repository_unique_csize: Option<i64>,
+    /// The uncompressed size of all unique chunks in bytes
+    pub repository_unique_size: Option<i64>,
+
+    /// The point in time the drone started the run, according to the clock of the drone
+    pub started_at: Option<chrono::NaiveDateTime>,
+    /// The point in time the drone finished the run, according to the clock of the drone
+    pub finished_at: Option<chrono::NaiveDateTime>,
+    /// The point in time the pre hook was started, according to the clock of the drone
+    pub pre_hook_started_at: Option<chrono::NaiveDateTime>,
+    /// The point in time the pre hook finished, according to the clock of the drone
+    pub pre_hook_finished_at: Option<chrono::NaiveDateTime>,
+    /// The point in time the post hook was started, according to the clock of the drone
+    pub post_hook_started_at: Option<chrono::NaiveDateTime>,
+    /// The point in time the post hook finished, according to the clock of the drone
+    pub post_hook_finished_at: Option<chrono::NaiveDateTime>,
+
+    /// The skew of the clock of the drone in seconds, when it sent the report
+    ///
+    /// Positive values mean that the clock of the drone is ahead.
+    /// Not set if the drone didn't report the point in time it sent the report.
+    pub clock_skew: Option<i64>,
+
     /// The point in time, this stats were collected
+    ///
+    /// This is the end of the run reported by the drone, corrected by the clock skew.
+    /// If the drone didn't report it, it's the point in time the stats were received.
+    /// Stats that were received before it was recorded get it set by the retention task.
+    pub collected_at: Option<chrono::NaiveDateTime>,
+
+    /// The point in time, this stats were received
     #[rorm(auto_create_time)]
     pub created_at: chrono::NaiveDateTime,
 }
@@ -96,4 +218,245 @@ pub(crate) struct DroneStatsInsert {
     pub(crate) compressed_size: i64,
     pub(crate) deduplicated_size: i64,
     pub(crate) nfiles: i64,
+    pub(crate) anomaly: bool,
+    pub(crate) warnings: i64,
+    pub(crate) files_added: Option<i64>,
+    pub(crate) files_modified: Option<i64>,
+    pub(crate) files_unchanged: Option<i64>,
+    pub(crate) changed_paths: Option<String>,
+    pub(crate) changed_paths_truncated: bool,
+    pub(crate) archive_name: Option<String>,
+    pub(crate) archive_id: Option<String>,
+    pub(crate) archive_start: Option<chrono::NaiveDateTime>,
+    pub(crate) archive_end: Option<chrono::NaiveDateTime>,
+    pub(crate) repository_total_chunks: Option<i64>,
+    pub(crate) repository_total_csize: Option<i64>,
+    pub(crate) repository_total_size: Option<i64>,
+    pub(crate) repository_total_unique_chunks: Option<i64>,
+    pub(crate) repository_unique_csize: Option<i64>,
+    pub(crate) repository_unique_size: Option<i64>,
+    pub(crate) started_at: Option<chrono::NaiveDateTime>,
+    pub(crate) finished_at: Option<chrono::NaiveDateTime>,
+    pub(crate) pre_hook_started_at: Option<chrono::NaiveDateTime>,
+    pub(crate) pre_hook_finished_at: Option<chrono::NaiveDateTime>,
+    pub(crate) post_hook_started_at: Option<chrono::NaiveDateTime>,
+    pub(crate) post_hook_finished_at: Option<chrono::NaiveDateTime>,
+    pub(crate) clock_skew: Option<i64>,
+    pub(crate) collected_at: Option<chrono::NaiveDateTime>,
+}
+
+/// The values of the stats of a drone that are compared with the baseline for anomalies
+#[derive(Patch, Copy, Clone, Debug)]
+#[rorm(model = "DroneStats")]
+pub(crate) struct DroneStatsBaseline {
+    pub(crate) original_size: i64,
+    pub(crate) nfiles: i64,
+    pub(crate) deduplicated_size: i64,
+    pub(crate) create_duration: i64,
+}
+
+/// The stats of a drone without the potentially large list of changed paths
+#[derive(Patch)]
+#[rorm(model = "DroneStats")]
+pub(crate) struct DroneStatsSample {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) pre_hook_duration: Option<i64>,
+    pub(crate) post_hook_duration: Option<i64>,
+    pub(crate) create_duration: i64,
+    pub(crate) complete_duration: i64,
+    pub(crate) original_size: i64,
+    pub(crate) compressed_size: i64,
+    pub(crate) deduplicated_size: i64,
+    pub(crate) nfiles: i64,
+    pub(crate) anomaly: bool,
+    pub(crate) warnings: i64,
+    pub(crate) files_added: Option<i64>,
+    pub(crate) files_modified: Option<i64>,
+    pub(crate) files_unchanged: Option<i64>,
+    pub(crate) changed_paths_truncated: bool,
+    pub(crate) archive_name: Option<String>,
+    pub(crate) archive_id: Option<String>,
+    pub(crate) archive_start: Option<chrono::NaiveDateTime>,
+    pub(crate) archive_end: Option<chrono::NaiveDateTime>,
+    pub(crate) repository_total_chunks: Option<i64>,
+    pub(crate) repository_total_csize: Option<i64>,
+    pub(crate) repository_total_size: Option<i64>,
+    pub(crate) repository_total_unique_chunks: Option<i64>,
+    pub(crate) repository_unique_csize: Option<i64>,
+    pub(crate) repository_unique_size: Option<i64>,
+    pub(crate) started_at: Option<chrono::NaiveDateTime>,
+    pub(crate) finished_at: Option<chrono::NaiveDateTime>,
+    pub(crate) pre_hook_started_at: Option<chrono::NaiveDateTime>,
+    pub(crate) pre_hook_finished_at: Option<chrono::NaiveDateTime>,
+    pub(crate) post_hook_started_at: Option<chrono::NaiveDateTime>,
+    pub(crate) post_hook_finished_at: Option<chrono::NaiveDateTime>,
+    pub(crate) clock_skew: Option<i64>,
+    pub(crate) collected_at: Option<chrono::NaiveDateTime>,
+    pub(crate) created_at: chrono::NaiveDateTime,
+}
+
+/// A warning borg logged while a drone created an archive
+#[derive(Model)]
+pub struct DroneWarning {
+    /// The primary key of the warning
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The drone that reported the warning
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub drone: ForeignModel<Drone>,
+
+    /// The stats of the archive creation the warning was logged in
+    ///
+    /// Unset once the stats were rolled into an aggregate by the retention task.
+    #[rorm(on_update = "Cascade", on_delete = "SetNull")]
+    pub stats: Option<ForeignModel<DroneStats>>,
+
+    /// The message of the warning
+    #[rorm(max_length = 4096)]
+    pub message: String,
+
+    /// The path the warning is about
+    #[rorm(max_length = 4096)]
+    pub path: Option<String>,
+
+    /// The point in time the warning was reported
+    #[rorm(auto_create_time)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Patch)]
+#[rorm(model = "DroneWarning")]
+pub(crate) struct DroneWarningInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) stats: Option<ForeignModel<DroneStats>>,
+    pub(crate) message: String,
+    pub(crate) path: Option<String>,
+}
+
+/// The period stats are aggregated over
+#[derive(DbEnum, Serialize, ToSchema, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AggregationPeriod {
+    /// The stats of a single day
+    Day,
+    /// The stats of a single week, starting on monday
+    Week,
+}
+
+/// The stats of a drone, aggregated over a period of time
+///
+/// Old [DroneStats] are rolled into daily and later into weekly aggregates.
+/// The values are the averages of all aggregated stats,
+/// their minimums and maximums are stored alongside.
+/// Aggregates that were created before the minimums and maximums were recorded don't have them.
+#[derive(Model)]
+pub struct DroneStatsAggregate {
+    /// The primary key of the aggregate
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The drone that owns this stats
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub drone: ForeignModel<Drone>,
+
+    /// The period the stats were aggregated over
+    pub period: AggregationPeriod,
+    /// The number of stats that were aggregated
+    pub count: i64,
+
+    /// The duration in seconds that the pre hook took to execute
+    pub pre_hook_duration: Option<i64>,
+    /// The duration in seconds that the post hook took to execute
+    pub post_hook_duration: Option<i64>,
+    /// The duration in seconds that the archive creation took
+    pub create_duration: i64,
+    /// The duration in seconds that the complete operation took
+    pub complete_duration: i64,
+    /// Original file size in bytes
+    pub original_size: i64,
+    /// Compressed file size in bytes
+    pub compressed_size: i64,
+    /// Deduplicated file size in bytes
+    pub deduplicated_size: i64,
+    /// Number of archived files
+    pub nfiles: i64,
+
+    /// The number of aggregated stats that reported the duration of the pre hook
+    pub pre_hook_count: Option<i64>,
+    /// The number of aggregated stats that reported the duration of the post hook
+    pub post_hook_count: Option<i64>,
+
+    /// The minimum duration of the pre hook
+    pub pre_hook_duration_min: Option<i64>,
+    /// The maximum duration of the pre hook
+    pub pre_hook_duration_max: Option<i64>,
+    /// The minimum duration of the post hook
+    pub post_hook_duration_min: Option<i64>,
+    /// The maximum duration of the post hook
+    pub post_hook_duration_max: Option<i64>,
+    /// The minimum duration of the archive creation
+    pub create_duration_min: Option<i64>,
+    /// The maximum duration of the archive creation
+    pub create_duration_max: Option<i64>,
+    /// The minimum duration of the complete operation
+    pub complete_duration_min: Option<i64>,
+    /// The maximum duration of the complete operation
+    pub complete_duration_max: Option<i64>,
+    /// The minimum original size
+    pub original_size_min: Option<i64>,
+    /// The maximum original size
+    pub original_size_max: Option<i64>,
+    /// The minimum compressed size
+    pub compressed_size_min: Option<i64>,
+    /// The maximum compressed size
+    pub compressed_size_max: Option<i64>,
+    /// The minimum deduplicated size
+    pub deduplicated_size_min: Option<i64>,
+    /// The maximum deduplicated size
+    pub deduplicated_size_max: Option<i64>,
+    /// The minimum number of archived files
+    pub nfiles_min: Option<i64>,
+    /// The maximum number of archived files
+    pub nfiles_max: Option<i64>,
+
+    /// The start of the period
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Patch)]
+#[rorm(model = "DroneStatsAggregate")]
+pub(crate) struct DroneStatsAggregateInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) period: AggregationPeriod,
+    pub(crate) count: i64,
+    pub(crate) pre_hook_duration: Option<i64>,
+    pub(crate) post_hook_duration: Option<i64>,
+    pub(crate) create_duration: i64,
+    pub(crate) complete_duration: i64,
+    pub(crate) original_size: i64,
+    pub(crate) compressed_size: i64,
+    pub(crate) deduplicated_size: i64,
+    pub(crate) nfiles: i64,
+    pub(crate) pre_hook_count: Option<i64>,
+    pub(crate) post_hook_count: Option<i64>,
+    pub(crate) pre_hook_duration_min: Option<i64>,
+    pub(crate) pre_hook_duration_max: Option<i64>,
+    pub(crate) post_hook_duration_min: Option<i64>,
+    pub(crate) post_hook_duration_max: Option<i64>,
+    pub(crate) create_duration_min: Option<i64>,
+    pub(crate) create_duration_max: Option<i64>,
+    pub(crate) complete_duration_min: Option<i64>,
+    pub(crate) complete_duration_max: Option<i64>,
+    pub(crate) original_size_min: Option<i64>,
+    pub(crate) original_size_max: Option<i64>,
+    pub(crate) compressed_size_min: Option<i64>,
+    pub(crate) compressed_size_max: Option<i64>,
+    pub(crate) deduplicated_size_min: Option<i64>,
+    pub(crate) deduplicated_size_max: Option<i64>,
+    pub(crate) nfiles_min: Option<i64>,
+    pub(crate) nfiles_max: Option<i64>,
+    pub(crate) created_at: chrono::NaiveDateTime,
 }
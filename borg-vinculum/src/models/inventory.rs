@@ -0,0 +1,56 @@
+use rorm::fields::ForeignModel;
+use rorm::{Model, Patch};
+use uuid::Uuid;
+
+use crate::models::Drone;
+
+/// The inventory of the host of a drone
+///
+/// A new entry is stored every time a drone reports an inventory that differs from the
+/// last reported one, so the entries form the history of changes to the host.
+#[derive(Model)]
+pub struct DroneInventory {
+    /// The primary key of the inventory
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The drone that reported the inventory
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub drone: ForeignModel<Drone>,
+
+    /// The hostname of the host
+    #[rorm(max_length = 255)]
+    pub hostname: Option<String>,
+    /// The operating system of the host
+    #[rorm(max_length = 255)]
+    pub os: Option<String>,
+    /// The kernel release of the host
+    #[rorm(max_length = 255)]
+    pub kernel: Option<String>,
+    /// The version of borg
+    #[rorm(max_length = 255)]
+    pub borg_version: Option<String>,
+    /// The version of borg-drone
+    #[rorm(max_length = 255)]
+    pub drone_version: Option<String>,
+    /// The compression the drone creates archives with
+    #[rorm(max_length = 255)]
+    pub compression: Option<String>,
+
+    /// The point in time the inventory was reported
+    #[rorm(auto_create_time)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Patch)]
+#[rorm(model = "DroneInventory")]
+pub(crate) struct DroneInventoryInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) hostname: Option<String>,
+    pub(crate) os: Option<String>,
+    pub(crate) kernel: Option<String>,
+    pub(crate) borg_version: Option<String>,
+    pub(crate) drone_version: Option<String>,
+    pub(crate) compression: Option<String>,
+}
@@ -1,7 +1,19 @@
 //! The models for borg-vinculum
 
 pub use account::*;
+pub use check::*;
 pub use drone::*;
+pub use inventory::*;
+pub use prune::*;
+pub use repository::*;
+pub use restore::*;
+pub use rule::*;
 
 mod account;
+mod check;
 mod drone;
+mod inventory;
+mod prune;
+mod repository;
+mod restore;
+mod rule;
@@ -0,0 +1,97 @@
+use common::PrunePolicy;
+use rorm::fields::ForeignModel;
+use rorm::{Model, Patch};
+use uuid::Uuid;
+
+use crate::models::Drone;
+
+/// The retention policy of a drone
+///
+/// The policy is retrieved by drones that prune their repository with `borg-drone prune`
+/// and have no policy in their config.
+#[derive(Model)]
+pub struct DronePrunePolicy {
+    /// The primary key of the policy
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The drone the policy applies to
+    #[rorm(on_update = "Cascade", on_delete = "Cascade", unique)]
+    pub drone: ForeignModel<Drone>,
+
+    /// Keep all archives within this interval, e.g. `7d`
+    #[rorm(max_length = 16)]
+    pub keep_within: Option<String>,
+    /// The number of hourly archives to keep
+    pub keep_hourly: Option<i32>,
+    /// The number of daily archives to keep
+    pub keep_daily: Option<i32>,
+    /// The number of weekly archives to keep
+    pub keep_weekly: Option<i32>,
+    /// The number of monthly archives to keep
+    pub keep_monthly: Option<i32>,
+    /// The number of yearly archives to keep
+    pub keep_yearly: Option<i32>,
+}
+
+#[derive(Patch)]
+#[rorm(model = "DronePrunePolicy")]
+pub(crate) struct DronePrunePolicyInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) keep_within: Option<String>,
+    pub(crate) keep_hourly: Option<i32>,
+    pub(crate) keep_daily: Option<i32>,
+    pub(crate) keep_weekly: Option<i32>,
+    pub(crate) keep_monthly: Option<i32>,
+    pub(crate) keep_yearly: Option<i32>,
+}
+
+/// The stats of a prune of the repository that was reported by a drone
+#[derive(Model)]
+pub struct DronePrune {
+    /// The primary key of the prune
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The drone that pruned its repository
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub drone: ForeignModel<Drone>,
+
+    /// The number of archives that were pruned
+    pub pruned: i64,
+    /// The number of archives that were kept
+    pub kept: i64,
+    /// The duration in seconds the prune took
+    pub duration: i64,
+
+    /// The point in time the prune was reported
+    #[rorm(auto_create_time)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Patch)]
+#[rorm(model = "DronePrune")]
+pub(crate) struct DronePruneInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) pruned: i64,
+    pub(crate) kept: i64,
+    pub(crate) duration: i64,
+}
+
+impl From<DronePrunePolicy> for PrunePolicy {
+    fn from(value: DronePrunePolicy) -> Self {
+        // The counts were validated to fit when the policy was set
+        let count = |x: Option<i32>| x.map(|x| x as u16);
+
+        PrunePolicy {
+            keep_within: value.keep_within,
+            keep_hourly: count(value.keep_hourly),
+            keep_daily: count(value.keep_daily),
+            keep_weekly: count(value.keep_weekly),
+            keep_monthly: count(value.keep_monthly),
+            keep_yearly: count(value.keep_yearly),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use rorm::fields::ForeignModel;
+use rorm::{Model, Patch};
+use uuid::Uuid;
+
+use crate::models::Drone;
+
+/// A snapshot of the information about the repository of a drone
+///
+/// The information is collected periodically using `borg info`.
+#[derive(Model)]
+pub struct RepositorySnapshot {
+    /// The primary key of the snapshot
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The drone the repository belongs to
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub drone: ForeignModel<Drone>,
+
+    /// The number of archives in the repository
+    pub archives: i64,
+    /// Number of chunks
+    pub total_chunks: i64,
+    /// Number of unique chunks
+    pub total_unique_chunks: i64,
+    /// Total uncompressed size of all chunks multiplied with their reference counts
+    pub total_size: i64,
+    /// Total compressed and encrypted size of all chunks multiplied with their reference counts
+    pub total_csize: i64,
+    /// Uncompressed size of all chunks
+    pub unique_size: i64,
+    /// Compressed and encrypted size of all chunks
+    ///
+    /// This is the size the repository occupies on disk.
+    pub unique_csize: i64,
+
+    /// The point in time the repository was last modified
+    pub last_modified: chrono::NaiveDateTime,
+
+    /// The point in time the snapshot was taken
+    #[rorm(auto_create_time)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Patch)]
+#[rorm(model = "RepositorySnapshot")]
+pub(crate) struct RepositorySnapshotInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) archives: i64,
+    pub(crate) total_chunks: i64,
+    pub(crate) total_unique_chunks: i64,
+    pub(crate) total_size: i64,
+    pub(crate) total_csize: i64,
+    pub(crate) unique_size: i64,
+    pub(crate) unique_csize: i64,
+    pub(crate) last_modified: chrono::NaiveDateTime,
+}
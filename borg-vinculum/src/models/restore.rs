@@ -0,0 +1,164 @@
+use rorm::fields::ForeignModel;
+use rorm::{DbEnum, Model, Patch};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::{Account, Drone};
+
+/// The kind of access to the contents of an archive
+#[derive(DbEnum, Serialize, ToSchema, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RestoreAction {
+    /// The contents of a directory were listed
+    List,
+    /// A single file was downloaded
+    Download,
+    /// The archive was exported as tar
+    ExportTar,
+}
+
+/// A recorded access to the contents of an archive
+#[derive(Model)]
+pub struct RestoreEvent {
+    /// The primary key of the event
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The account that accessed the archive
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub account: ForeignModel<Account>,
+
+    /// The drone the archive belongs to
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub drone: ForeignModel<Drone>,
+
+    /// The name of the archive
+    #[rorm(max_length = 255)]
+    pub archive: String,
+
+    /// The path in the archive that was accessed
+    ///
+    /// For exports, this are the exported paths separated by newlines.
+    #[rorm(max_length = 4096)]
+    pub path: String,
+
+    /// The kind of access
+    pub action: RestoreAction,
+
+    /// The point in time the archive was accessed
+    #[rorm(auto_create_time)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Patch)]
+#[rorm(model = "RestoreEvent")]
+pub(crate) struct RestoreEventInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) account: ForeignModel<Account>,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) archive: String,
+    pub(crate) path: String,
+    pub(crate) action: RestoreAction,
+}
+
+/// The result of a restore drill
+///
+/// A drill restores some random files of a random archive to verify that the backups
+/// of a drone can be restored.
+#[derive(Model)]
+pub struct RestoreDrill {
+    /// The primary key of the drill
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The drone whose repository was tested
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub drone: ForeignModel<Drone>,
+
+    /// The name of the archive the files were restored from
+    ///
+    /// Not set if no archive could be selected.
+    #[rorm(max_length = 255)]
+    pub archive: Option<String>,
+
+    /// Whether the files were restored successfully
+    pub success: bool,
+    /// The number of restored files
+    pub files: i64,
+    /// The number of restored bytes
+    pub bytes_restored: i64,
+    /// The duration in milliseconds the restore took
+    pub duration_ms: i64,
+
+    /// The error that occurred during the drill
+    #[rorm(max_length = 4096)]
+    pub error: Option<String>,
+
+    /// The point in time the drill was executed
+    #[rorm(auto_create_time)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Patch)]
+#[rorm(model = "RestoreDrill")]
+pub(crate) struct RestoreDrillInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) archive: Option<String>,
+    pub(crate) success: bool,
+    pub(crate) files: i64,
+    pub(crate) bytes_restored: i64,
+    pub(crate) duration_ms: i64,
+    pub(crate) error: Option<String>,
+}
+
+/// A restore of an archive that was executed on a drone and reported to the vinculum
+#[derive(Model)]
+pub struct DroneRestore {
+    /// The primary key of the restore
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The drone that restored the archive
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub drone: ForeignModel<Drone>,
+
+    /// The name of the restored archive
+    #[rorm(max_length = 255)]
+    pub archive: String,
+
+    /// The restored paths separated by newlines, empty if the whole archive was restored
+    #[rorm(max_length = 4096)]
+    pub paths: String,
+
+    /// The directory on the drone the archive was extracted into
+    #[rorm(max_length = 4096)]
+    pub target: String,
+
+    /// Whether the restore succeeded
+    pub success: bool,
+
+    /// The error that occurred during the restore
+    #[rorm(max_length = 4096)]
+    pub error: Option<String>,
+
+    /// The duration in seconds the restore took
+    pub duration: i64,
+
+    /// The point in time the restore was reported
+    #[rorm(auto_create_time)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Patch)]
+#[rorm(model = "DroneRestore")]
+pub(crate) struct DroneRestoreInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) archive: String,
+    pub(crate) paths: String,
+    pub(crate) target: String,
+    pub(crate) success: bool,
+    pub(crate) error: Option<String>,
+    pub(crate) duration: i64,
+}
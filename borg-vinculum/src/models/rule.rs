@@ -0,0 +1,113 @@
+use rorm::fields::ForeignModel;
+use rorm::{DbEnum, Model, Patch};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::{Drone, DroneStats};
+
+/// The value of the reported stats a rule is applied to
+#[derive(DbEnum, Deserialize, Serialize, ToSchema, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RuleField {
+    /// The duration in seconds that the pre hook took to execute
+    PreHookDuration,
+    /// The duration in seconds that the post hook took to execute
+    PostHookDuration,
+    /// The duration in seconds that the archive creation took
+    CreateDuration,
+    /// The duration in seconds that the complete operation took
+    CompleteDuration,
+    /// Original file size in bytes
+    OriginalSize,
+    /// Compressed file size in bytes
+    CompressedSize,
+    /// Deduplicated file size in bytes
+    DeduplicatedSize,
+    /// Number of archived files
+    Nfiles,
+}
+
+/// The comparison a rule requires between the reported and the configured value
+#[derive(DbEnum, Deserialize, Serialize, ToSchema, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RuleOperator {
+    /// The reported value must be less than the configured value
+    Less,
+    /// The reported value must be less than or equal to the configured value
+    LessOrEquals,
+    /// The reported value must be greater than the configured value
+    Greater,
+    /// The reported value must be greater than or equal to the configured value
+    GreaterOrEquals,
+}
+
+/// A rule the reported stats of a drone must satisfy
+#[derive(Model)]
+pub struct DroneRule {
+    /// The primary key of the rule
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The drone the rule is applied to
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub drone: ForeignModel<Drone>,
+
+    /// The value of the stats that is checked
+    pub field: RuleField,
+    /// The comparison that must be satisfied
+    pub operator: RuleOperator,
+    /// The value the reported value is compared with
+    pub value: i64,
+
+    /// The point in time the rule was deleted
+    ///
+    /// Deleted rules are no longer evaluated, but keep their violations.
+    pub deleted_at: Option<chrono::NaiveDateTime>,
+
+    /// The point in time the rule was created
+    #[rorm(auto_create_time)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Patch)]
+#[rorm(model = "DroneRule")]
+pub(crate) struct DroneRuleInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) drone: ForeignModel<Drone>,
+    pub(crate) field: RuleField,
+    pub(crate) operator: RuleOperator,
+    pub(crate) value: i64,
+}
+
+/// A violation of a [DroneRule] by reported stats
+#[derive(Model)]
+pub struct DroneRuleViolation {
+    /// The primary key of the violation
+    #[rorm(primary_key)]
+    pub uuid: Uuid,
+
+    /// The violated rule
+    #[rorm(on_update = "Cascade", on_delete = "Cascade")]
+    pub rule: ForeignModel<DroneRule>,
+
+    /// The stats that violated the rule
+    ///
+    /// Unset once the stats were rolled into an aggregate by the retention task.
+    #[rorm(on_update = "Cascade", on_delete = "SetNull")]
+    pub stats: Option<ForeignModel<DroneStats>>,
+
+    /// The reported value that violated the rule
+    pub value: i64,
+
+    /// The point in time the violation occurred
+    #[rorm(auto_create_time)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Patch)]
+#[rorm(model = "DroneRuleViolation")]
+pub(crate) struct DroneRuleViolationInsert {
+    pub(crate) uuid: Uuid,
+    pub(crate) rule: ForeignModel<DroneRule>,
+    pub(crate) stats: Option<ForeignModel<DroneStats>>,
+    pub(crate) value: i64,
+}
@@ -0,0 +1,144 @@
+//! Detection of anomalies in the reported stats of drones
+
+use std::fmt::{Display, Formatter};
+
+use crate::config::AnomalyConfig;
+use crate::models::DroneStatsBaseline;
+
+/// A value of the reported stats that deviates from the baseline
+pub struct Anomaly {
+    /// The name of the value
+    pub field: &'static str,
+    /// The reported value
+    pub value: i64,
+    /// The average of the value in the baseline
+    pub baseline: f64,
+}
+
+impl Anomaly {
+    /// The relative deviation of the value from the baseline
+    pub fn deviation(&self) -> f64 {
+        (self.value as f64 - self.baseline) / self.baseline
+    }
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{field} is {value}, which deviates by {deviation:+.0}% from the baseline of {baseline:.0}",
+            field = self.field,
+            value = self.value,
+            deviation = self.deviation() * 100.0,
+            baseline = self.baseline,
+        )
+    }
+}
+
+/// Compare the reported stats with the baseline of the previous stats
+///
+/// If the baseline is smaller than configured, no anomalies are detected.
+pub(crate) fn detect_anomalies(
+    config: &AnomalyConfig,
+    baseline: &[DroneStatsBaseline],
+    stats: &DroneStatsBaseline,
+) -> Vec<Anomaly> {
+    if baseline.len() < config.min_baseline_size as usize {
+        return vec![];
+    }
+
+    let average = |get: fn(&DroneStatsBaseline) -> i64| {
+        baseline.iter().map(|x| get(x) as f64).sum::<f64>() / baseline.len() as f64
+    };
+
+    [
+        Anomaly {
+            field: "original_size",
+            value: stats.original_size,
+            baseline: average(|x| x.original_size),
+        },
+        Anomaly {
+            field: "nfiles",
+            value: stats.nfiles,
+            baseline: average(|x| x.nfiles),
+        },
+        Anomaly {
+            field: "deduplicated_size",
+            value: stats.deduplicated_size,
+            baseline: average(|x| x.deduplicated_size),
+        },
+        Anomaly {
+            field: "create_duration",
+            value: stats.create_duration,
+            baseline: average(|x| x.create_duration),
+        },
+    ]
+    .into_iter()
+    // A baseline of zero can't be compared relatively
+    .filter(|x| x.baseline > 0.0 && x.deviation().abs() > config.threshold)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(original_size: i64, nfiles: i64) -> DroneStatsBaseline {
+        DroneStatsBaseline {
+            original_size,
+            nfiles,
+            deduplicated_size: 100,
+            create_duration: 60,
+        }
+    }
+
+    #[test]
+    fn no_anomalies_below_min_baseline_size() {
+        let config = AnomalyConfig::default();
+        let baseline = vec![stats(1000, 10); config.min_baseline_size as usize - 1];
+
+        assert!(detect_anomalies(&config, &baseline, &stats(100_000, 1000)).is_empty());
+    }
+
+    #[test]
+    fn no_anomalies_within_threshold() {
+        let config = AnomalyConfig::default();
+        let baseline = vec![stats(1000, 10); 5];
+
+        assert!(detect_anomalies(&config, &baseline, &stats(1400, 6)).is_empty());
+    }
+
+    #[test]
+    fn anomalies_above_threshold() {
+        let config = AnomalyConfig::default();
+        let baseline = vec![stats(1000, 10); 5];
+
+        let anomalies = detect_anomalies(&config, &baseline, &stats(3000, 4));
+        let fields: Vec<_> = anomalies.iter().map(|x| x.field).collect();
+        assert_eq!(fields, ["original_size", "nfiles"]);
+        assert_eq!(anomalies[0].deviation(), 2.0);
+        assert_eq!(anomalies[1].deviation(), -0.6);
+    }
+
+    #[test]
+    fn zero_baseline_is_ignored() {
+        let config = AnomalyConfig::default();
+        let baseline = vec![stats(0, 10); 5];
+
+        assert!(detect_anomalies(&config, &baseline, &stats(1000, 10)).is_empty());
+    }
+
+    #[test]
+    fn display_anomaly() {
+        let anomaly = Anomaly {
+            field: "nfiles",
+            value: 150,
+            baseline: 100.0,
+        };
+
+        assert_eq!(
+            anomaly.to_string(),
+            "nfiles is 150, which deviates by +50% from the baseline of 100"
+        );
+    }
+}
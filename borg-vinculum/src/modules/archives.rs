@@ -0,0 +1,192 @@
+//! A cache for the archives of the repositories and their contents
+//!
+//! Retrieving the archives with their stats requires a call to `borg info` on the remote
+//! server, which is expensive for repositories with many archives.
+//! Listing a directory of an archive requires a `borg list` below that directory,
+//! which is expensive for directories with many files.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use borgbackup::output::info::InfoArchive;
+use chrono::{NaiveDateTime, Utc};
+use common::borg::ArchiveItem;
+use uuid::Uuid;
+
+/// The maximum number of items of directories in archives that are cached at once
+const MAX_CACHED_ITEMS: usize = 100_000;
+
+/// The archives of a repository at a point in time
+pub struct CachedArchives {
+    /// The archives of the repository
+    pub archives: Vec<InfoArchive>,
+    /// The point in time the archives were retrieved
+    pub retrieved_at: NaiveDateTime,
+    expires_at: Instant,
+}
+
+/// The items directly in a directory of an archive
+pub struct CachedDirectory {
+    /// The items in the directory
+    pub items: Vec<ArchiveItem>,
+    expires_at: Instant,
+}
+
+impl CachedDirectory {
+    /// Retrieve a single item of the directory
+    pub fn item(&self, path: &str) -> Option<&ArchiveItem> {
+        self.items.iter().find(|x| x.path == path)
+    }
+}
+
+/// The cached directories of archives and the number of items in them
+#[derive(Default)]
+struct CachedDirectories {
+    entries: HashMap<DirectoryKey, Arc<CachedDirectory>>,
+    items: usize,
+}
+
+impl CachedDirectories {
+    /// Remove a directory from the cache
+    fn remove(&mut self, key: &DirectoryKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.items -= entry.items.len();
+        }
+    }
+}
+
+/// The parent directory of a path in an archive, empty for top level items
+pub fn parent(path: &str) -> &str {
+    path.rsplit_once('/')
+        .map(|(parent, _)| parent)
+        .unwrap_or("")
+}
+
+/// The cache for the archives of the repositories, indexed by the uuid of the drone
+#[derive(Clone)]
+pub struct ArchiveCache {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<Uuid, Arc<CachedArchives>>>>,
+    directories: Arc<Mutex<CachedDirectories>>,
+}
+
+/// The uuid of a drone, the name of one of its archives and the path of a directory in it
+type DirectoryKey = (Uuid, String, String);
+
+impl ArchiveCache {
+    /// Create a new cache, that keeps entries for `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            directories: Arc::new(Mutex::new(CachedDirectories::default())),
+        }
+    }
+
+    /// Retrieve the cached archives of a drone, if they are not expired
+    pub fn get(&self, drone: &Uuid) -> Option<Arc<CachedArchives>> {
+        let mut entries = self.entries.lock().unwrap();
+
+        match entries.get(drone) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.clone()),
+            Some(_) => {
+                entries.remove(drone);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store the archives of a drone
+    pub fn insert(&self, drone: Uuid, archives: Vec<InfoArchive>) -> Arc<CachedArchives> {
+        let entry = Arc::new(CachedArchives {
+            archives,
+            retrieved_at: Utc::now().naive_utc(),
+            expires_at: Instant::now() + self.ttl,
+        });
+
+        if !self.ttl.is_zero() {
+            self.entries.lock().unwrap().insert(drone, entry.clone());
+        }
+
+        entry
+    }
+
+    /// Retrieve a cached directory of an archive of a drone, if it is not expired
+    pub fn get_directory(
+        &self,
+        drone: &Uuid,
+        archive: &str,
+        dir: &str,
+    ) -> Option<Arc<CachedDirectory>> {
+        let mut directories = self.directories.lock().unwrap();
+        let key = (*drone, archive.to_string(), dir.to_string());
+
+        match directories.entries.get(&key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.clone()),
+            Some(_) => {
+                directories.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store the items directly in a directory of an archive of a drone
+    ///
+    /// If the maximum number of cached items is exceeded, the oldest directories are evicted.
+    /// Directories with more items than the maximum are not cached.
+    pub fn insert_directory(
+        &self,
+        drone: Uuid,
+        archive: String,
+        dir: String,
+        items: Vec<ArchiveItem>,
+    ) -> Arc<CachedDirectory> {
+        let entry = Arc::new(CachedDirectory {
+            items,
+            expires_at: Instant::now() + self.ttl,
+        });
+
+        if !self.ttl.is_zero() && entry.items.len() <= MAX_CACHED_ITEMS {
+            let mut directories = self.directories.lock().unwrap();
+            let key = (drone, archive, dir);
+            directories.remove(&key);
+
+            while directories.items + entry.items.len() > MAX_CACHED_ITEMS {
+                let oldest = directories
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, x)| x.expires_at)
+                    .map(|(key, _)| key.clone());
+                match oldest {
+                    Some(oldest) => directories.remove(&oldest),
+                    None => break,
+                }
+            }
+
+            directories.items += entry.items.len();
+            directories.entries.insert(key, entry.clone());
+        }
+
+        entry
+    }
+
+    /// Remove the archives of a drone and their contents from the cache
+    ///
+    /// This should be called, when the archives of the repository are known to have changed.
+    pub fn invalidate(&self, drone: &Uuid) {
+        self.entries.lock().unwrap().remove(drone);
+        let mut directories = self.directories.lock().unwrap();
+        let keys: Vec<_> = directories
+            .entries
+            .keys()
+            .filter(|(uuid, _, _)| uuid == drone)
+            .cloned()
+            .collect();
+        for key in keys {
+            directories.remove(&key);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//! Execution of the borg commands that are not covered by [borgbackup::asynchronous]
+//!
+//! The commands that are needed by the drones as well are found in [common::borg].
+
+use std::io;
+use std::process::Stdio;
+
+use borgbackup::common::CommonOptions;
+use borgbackup::output::common::Repository;
+use borgbackup::output::info::InfoArchive;
+use borgbackup::output::list::ListArchive;
+use common::borg::{
+    archive_spec, borg_command, check_output, check_status, path_pattern, ArchiveItem, BorgError,
+};
+use futures::{Stream, StreamExt};
+use log::warn;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use tokio::io::AsyncReadExt;
+use tokio::process::{Child, ChildStdout};
+use tokio::task::JoinHandle;
+
+use crate::config::BorgConfig;
+use crate::modules::archives::parent;
+
+/// Build the [CommonOptions] to access the repositories of the drones
+pub fn common_options(config: &BorgConfig) -> CommonOptions {
+    CommonOptions {
+        local_path: Some(config.borg_path.clone()),
+        remote_path: config.borg_remote_path.clone(),
+        upload_ratelimit: None,
+        rsh: Some(format!(
+            "ssh -i {} -o 'StrictHostKeyChecking accept-new'",
+            shlex::quote(&config.ssh_key_path)
+        )),
+    }
+}
+
+/// The output of `borg list` for a repository
+#[derive(Deserialize, Debug, Clone)]
+pub struct ArchiveList {
+    /// Information about the repository
+    pub repository: Repository,
+    /// The archives in the repository
+    pub archives: Vec<ListArchive>,
+}
+
+/// Retrieve the archives of a repository
+pub async fn list_archives(
+    common_options: &CommonOptions,
+    repository: &str,
+    passphrase: &str,
+) -> Result<ArchiveList, BorgError> {
+    let res = borg_command(
+        common_options,
+        passphrase,
+        &format!("list --json {}", shlex::quote(repository)),
+    )?
+    .output()
+    .await
+    .map_err(BorgError::CommandFailed)?;
+
+    check_output(&res)?;
+
+    Ok(serde_json::from_slice(&res.stdout)?)
+}
+
+/// The output of `borg info` for the archives of a repository
+#[derive(Deserialize, Debug, Clone)]
+pub struct ArchivesInfo {
+    /// Information about the repository
+    pub repository: Repository,
+    /// The archives in the repository with their stats
+    pub archives: Vec<InfoArchive>,
+}
+
+/// Retrieve the information about all archives of a repository
+///
+/// This is expensive, as borg calculates the stats of every archive.
+pub async fn info_archives(
+    common_options: &CommonOptions,
+    repository: &str,
+    passphrase: &str,
+) -> Result<ArchivesInfo, BorgError> {
+    let res = borg_command(
+        common_options,
+        passphrase,
+        &format!(
+            "info --json --glob-archives '*' {}",
+            shlex::quote(repository)
+        ),
+    )?
+    .output()
+    .await
+    .map_err(BorgError::CommandFailed)?;
+
+    check_output(&res)?;
+
+    Ok(serde_json::from_slice(&res.stdout)?)
+}
+
+/// Stream the items of an archive from `borg list` and pass every item to `f`
+///
+/// If `path` is given, only the item at `path` and the items below it are listed.
+/// The items are parsed while they are read, so the listing is never kept in memory.
+async fn for_each_archive_item(
+    common_options: &CommonOptions,
+    repository: &str,
+    archive: &str,
+    passphrase: &str,
+    path: Option<&str>,
+    mut f: impl FnMut(ArchiveItem),
+) -> Result<(), BorgError> {
+    let mut args = format!("list --json-lines -- {}", archive_spec(repository, archive));
+    if let Some(path) = path {
+        args.push(' ');
+        args.push_str(&path_pattern(path));
+    }
+
+    let stream = stream_stdout(borg_command(common_options, passphrase, &args)?)?;
+    futures::pin_mut!(stream);
+
+    let mut buf = vec![];
+    while let Some(chunk) = stream.next().await {
+        buf.extend(chunk.map_err(BorgError::CommandFailed)?);
+
+        while let Some(end) = buf.iter().position(|x| *x == b'\n') {
+            let line: Vec<u8> = buf.drain(..=end).collect();
+            if line.len() == 1 {
+                continue;
+            }
+
+            f(serde_json::from_slice(&line)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Retrieve the items directly in a directory of an archive
+///
+/// The root of the archive is the empty path.
+/// Returns `None` if there is no such directory in the archive.
+pub async fn list_directory(
+    common_options: &CommonOptions,
+    repository: &str,
+    archive: &str,
+    passphrase: &str,
+    dir: &str,
+) -> Result<Option<Vec<ArchiveItem>>, BorgError> {
+    let mut exists = dir.is_empty();
+    let mut items = vec![];
+
+    let path = (!dir.is_empty()).then_some(dir);
+    for_each_archive_item(
+        common_options,
+        repository,
+        archive,
+        passphrase,
+        path,
+        |item| {
+            if item.path == dir {
+                exists = item.kind == "d";
+            } else if parent(&item.path) == dir {
+                items.push(item);
+            }
+        },
+    )
+    .await?;
+
+    Ok(exists.then_some(items))
+}
+
+/// Retrieve a single item of an archive
+pub async fn archive_item(
+    common_options: &CommonOptions,
+    repository: &str,
+    archive: &str,
+    passphrase: &str,
+    path: &str,
+) -> Result<Option<ArchiveItem>, BorgError> {
+    let mut found = None;
+    for_each_archive_item(
+        common_options,
+        repository,
+        archive,
+        passphrase,
+        Some(path),
+        |item| {
+            if item.path == path {
+                found = Some(item);
+            }
+        },
+    )
+    .await?;
+
+    Ok(found)
+}
+
+/// Pick up to `count` random regular files of an archive
+///
+/// The items are streamed from `borg list` and sampled while they are read,
+/// so the listing of the archive is never kept in memory.
+pub async fn sample_archive_files(
+    common_options: &CommonOptions,
+    repository: &str,
+    archive: &str,
+    passphrase: &str,
+    count: usize,
+) -> Result<Vec<ArchiveItem>, BorgError> {
+    // The sampler is held across awaits, so the thread local rng can't be used
+    let mut rng = StdRng::from_entropy();
+    let mut sample = Vec::with_capacity(count);
+    let mut files = 0;
+
+    for_each_archive_item(
+        common_options,
+        repository,
+        archive,
+        passphrase,
+        None,
+        |item| {
+            if item.kind != "-" {
+                return;
+            }
+
+            // Reservoir sampling, every file has the same chance to be picked
+            files += 1;
+            if sample.len() < count {
+                sample.push(item);
+            } else {
+                let index = rng.gen_range(0..files);
+                if index < count {
+                    sample[index] = item;
+                }
+            }
+        },
+    )
+    .await?;
+
+    Ok(sample)
+}
+
+/// The size of the chunks the output of borg is streamed in
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The state of a running borg process whose stdout is streamed
+struct OutputStream {
+    child: Child,
+    stdout: ChildStdout,
+    stderr: JoinHandle<io::Result<Vec<u8>>>,
+}
+
+impl OutputStream {
+    /// Wait for borg to exit and check its exit status
+    async fn finish(mut self) -> Result<(), BorgError> {
+        let status = self.child.wait().await.map_err(BorgError::CommandFailed)?;
+        let stderr = self
+            .stderr
+            .await
+            .map_err(|err| BorgError::CommandFailed(io::Error::new(io::ErrorKind::Other, err)))?
+            .map_err(BorgError::CommandFailed)?;
+
+        check_status(status, &stderr)
+    }
+}
+
+/// Spawn borg and stream its stdout
+///
+/// borg is killed when the stream is dropped, e.g. if the client disconnects.
+/// If borg fails, the stream ends with an error.
+fn stream_stdout(
+    mut cmd: tokio::process::Command,
+) -> Result<impl Stream<Item = Result<Vec<u8>, io::Error>>, BorgError> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(BorgError::CommandFailed)?;
+
+    let stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    // stderr must be drained, otherwise borg could block on writing to it
+    let stderr = tokio::spawn(async move {
+        let mut buf = vec![];
+        stderr.read_to_end(&mut buf).await.map(|_| buf)
+    });
+
+    let state = OutputStream {
+        child,
+        stdout,
+        stderr,
+    };
+
+    Ok(futures::stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+
+        let mut buf = vec![0; STREAM_CHUNK_SIZE];
+        match state.stdout.read(&mut buf).await {
+            Ok(0) => match state.finish().await {
+                Ok(()) => None,
+                Err(err) => {
+                    warn!("Borg failed while streaming its output: {err}");
+                    Some((Err(io::Error::new(io::ErrorKind::Other, err)), None))
+                }
+            },
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(buf), Some(state)))
+            }
+            Err(err) => Some((Err(err), None)),
+        }
+    }))
+}
+
+/// Extract a single file of an archive and stream its contents
+pub fn extract_file(
+    common_options: &CommonOptions,
+    repository: &str,
+    archive: &str,
+    passphrase: &str,
+    path: &str,
+) -> Result<impl Stream<Item = Result<Vec<u8>, io::Error>>, BorgError> {
+    stream_stdout(borg_command(
+        common_options,
+        passphrase,
+        &format!(
+            "extract --stdout -- {} {}",
+            archive_spec(repository, archive),
+            path_pattern(path)
+        ),
+    )?)
+}
+
+/// Export an archive as tar and stream it
+///
+/// If `paths` is not empty, only the given paths are exported.
+pub fn export_tar(
+    common_options: &CommonOptions,
+    repository: &str,
+    archive: &str,
+    passphrase: &str,
+    paths: &[&str],
+) -> Result<impl Stream<Item = Result<Vec<u8>, io::Error>>, BorgError> {
+    let mut args = format!("export-tar -- {} -", archive_spec(repository, archive));
+    for path in paths {
+        args.push(' ');
+        args.push_str(&path_pattern(path));
+    }
+
+    stream_stdout(borg_command(common_options, passphrase, &args)?)
+}
+
+/// Extract paths of an archive into `target_dir`
+pub async fn extract_paths(
+    common_options: &CommonOptions,
+    repository: &str,
+    archive: &str,
+    passphrase: &str,
+    paths: &[&str],
+    target_dir: &std::path::Path,
+) -> Result<(), BorgError> {
+    let mut args = format!("extract -- {}", archive_spec(repository, archive));
+    for path in paths {
+        args.push(' ');
+        args.push_str(&path_pattern(path));
+    }
+
+    let res = borg_command(common_options, passphrase, &args)?
+        .current_dir(target_dir)
+        .output()
+        .await
+        .map_err(BorgError::CommandFailed)?;
+
+    check_output(&res)
+}
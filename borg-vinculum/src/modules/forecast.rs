@@ -0,0 +1,138 @@
+//! Forecasting of the growth of the repositories of drones
+
+use chrono::{Duration, NaiveDateTime};
+use rorm::transaction::Transaction;
+use rorm::{and, query, Model};
+use uuid::Uuid;
+
+use crate::models::{DroneStats, RepositorySnapshot};
+
+/// The forecast of the size of a repository
+pub struct Forecast {
+    /// The current size of the repository in bytes
+    pub current_size: i64,
+    /// The estimated growth of the repository in bytes per day
+    pub growth_per_day: f64,
+    /// The point in time the capacity limit is estimated to be reached
+    ///
+    /// `None` if there is no capacity limit or the repository doesn't grow.
+    pub capacity_reached_at: Option<NaiveDateTime>,
+}
+
+impl Forecast {
+    /// Create a forecast from the current size and the estimated growth of a repository
+    pub fn new(
+        current_size: i64,
+        growth_per_day: f64,
+        capacity_limit: Option<i64>,
+        now: NaiveDateTime,
+    ) -> Self {
+        let capacity_reached_at = capacity_limit.and_then(|limit| {
+            if current_size >= limit {
+                Some(now)
+            } else if growth_per_day > 0.0 {
+                let days = (limit - current_size) as f64 / growth_per_day;
+                // Everything beyond that is not a meaningful forecast anymore
+                (days < 365.0 * 100.0).then(|| now + Duration::seconds((days * 86400.0) as i64))
+            } else {
+                None
+            }
+        });
+
+        Self {
+            current_size,
+            growth_per_day,
+            capacity_reached_at,
+        }
+    }
+
+    /// The estimated size of the repository in the given number of days
+    pub fn size_in_days(&self, days: i64) -> i64 {
+        (self.current_size as f64 + self.growth_per_day * days as f64).max(0.0) as i64
+    }
+}
+
+/// Estimate the growth of the repository of a drone in bytes per day.
+///
+/// The sizes of the repository on disk are taken from the [RepositorySnapshot]s
+/// and the repository totals the drone reported with its stats.
+/// The growth is the slope of a linear regression over these sizes.
+pub(crate) async fn estimate_growth(
+    tx: &mut Transaction,
+    drone: &Uuid,
+) -> Result<f64, rorm::Error> {
+    let snapshots = query!(
+        &mut *tx,
+        (
+            RepositorySnapshot::F.created_at,
+            RepositorySnapshot::F.unique_csize,
+        )
+    )
+    .condition(RepositorySnapshot::F.drone.equals(drone.as_ref()))
+    .all()
+    .await?;
+    let reported = query!(
+        &mut *tx,
+        (
+            DroneStats::F.collected_at,
+            DroneStats::F.repository_unique_csize,
+        )
+    )
+    .condition(and!(
+        DroneStats::F.drone.equals(drone.as_ref()),
+        DroneStats::F.collected_at.is_not_null(),
+        DroneStats::F.repository_unique_csize.is_not_null()
+    ))
+    .all()
+    .await?;
+
+    let mut sizes: Vec<(NaiveDateTime, i64)> = snapshots
+        .into_iter()
+        .chain(
+            reported
+                .into_iter()
+                .filter_map(|(collected_at, size)| Some((collected_at?, size?))),
+        )
+        .collect();
+    sizes.sort_by_key(|(created_at, _)| *created_at);
+
+    let Some(first) = sizes.first().map(|(created_at, _)| *created_at) else {
+        return Ok(0.0);
+    };
+
+    let points: Vec<(f64, f64)> = sizes
+        .iter()
+        .map(|(created_at, size)| {
+            let days = (*created_at - first).num_seconds() as f64 / 86400.0;
+            (days, *size as f64)
+        })
+        .collect();
+
+    Ok(linear_regression(&points))
+}
+
+/// Calculate the slope of the least squares regression line through the given points
+///
+/// Returns `0` if the slope is not defined.
+fn linear_regression(points: &[(f64, f64)]) -> f64 {
+    if points.len() < 2 {
+        return 0.0;
+    }
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+    let (covariance, variance) = points.iter().fold((0.0, 0.0), |(cov, var), (x, y)| {
+        (
+            cov + (x - mean_x) * (y - mean_y),
+            var + (x - mean_x) * (x - mean_x),
+        )
+    });
+
+    if variance == 0.0 {
+        0.0
+    } else {
+        covariance / variance
+    }
+}
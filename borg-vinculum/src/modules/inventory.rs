@@ -0,0 +1,95 @@
+//! Tracking of the inventory of the hosts drones run on
+
+use common::HostInventory;
+use rorm::fields::ForeignModelByField;
+use rorm::{insert, query, update, Database, Model};
+use uuid::Uuid;
+
+use crate::models::{Drone, DroneInventoryInsert};
+
+/// The maximum length of the stored values of an inventory
+const MAX_INVENTORY_FIELD_LENGTH: usize = 255;
+
+/// The hostname of a drone changed
+pub struct HostnameChange {
+    /// The drone that reported the new hostname
+    pub drone: Drone,
+    /// The previously reported hostname
+    pub previous: String,
+    /// The newly reported hostname
+    pub current: String,
+}
+
+/// Store the inventory reported by a drone.
+///
+/// The inventory is only stored, if it differs from the last reported one.
+/// If the drone reported a different hostname than before, the change is returned.
+pub(crate) async fn update_inventory(
+    db: &Database,
+    drone: &Uuid,
+    host: &HostInventory,
+) -> Result<Option<HostnameChange>, rorm::Error> {
+    let truncate = |x: &str| {
+        x.chars()
+            .take(MAX_INVENTORY_FIELD_LENGTH)
+            .collect::<String>()
+    };
+    let inventory = DroneInventoryInsert {
+        uuid: Uuid::new_v4(),
+        drone: ForeignModelByField::Key(*drone),
+        hostname: host.hostname.as_deref().map(truncate),
+        os: host.os.as_deref().map(truncate),
+        kernel: host.kernel.as_deref().map(truncate),
+        borg_version: host.borg_version.as_deref().map(truncate),
+        drone_version: Some(truncate(&host.drone_version)),
+        compression: Some(truncate(&host.compression)),
+    };
+
+    let mut tx = db.start_transaction().await?;
+
+    let drone = query!(&mut tx, Drone)
+        .condition(Drone::F.uuid.equals(drone.as_ref()))
+        .one()
+        .await?;
+
+    let unchanged = drone.hostname == inventory.hostname
+        && drone.os == inventory.os
+        && drone.kernel == inventory.kernel
+        && drone.borg_version == inventory.borg_version
+        && drone.drone_version == inventory.drone_version
+        && drone.compression == inventory.compression;
+    if unchanged {
+        tx.commit().await?;
+        return Ok(None);
+    }
+
+    update!(&mut tx, Drone)
+        .condition(Drone::F.uuid.equals(drone.uuid.as_ref()))
+        .set(Drone::F.hostname, inventory.hostname.clone())
+        .set(Drone::F.os, inventory.os.clone())
+        .set(Drone::F.kernel, inventory.kernel.clone())
+        .set(Drone::F.borg_version, inventory.borg_version.clone())
+        .set(Drone::F.drone_version, inventory.drone_version.clone())
+        .set(Drone::F.compression, inventory.compression.clone())
+        .exec()
+        .await?;
+
+    insert!(&mut tx, DroneInventoryInsert)
+        .return_nothing()
+        .single(&inventory)
+        .await?;
+
+    tx.commit().await?;
+
+    // A hostname that could not be determined is no change
+    let change = match (&drone.hostname, inventory.hostname) {
+        (Some(previous), Some(current)) if *previous != current => Some(HostnameChange {
+            previous: previous.clone(),
+            current,
+            drone,
+        }),
+        _ => None,
+    };
+
+    Ok(change)
+}
@@ -1,3 +1,10 @@
 //! All builtin modules that are used from borg vinculum are defined here
 
+pub mod anomaly;
+pub mod archives;
+pub mod borg;
+pub mod forecast;
+pub mod inventory;
 pub mod matrix;
+pub mod rules;
+pub mod stats;
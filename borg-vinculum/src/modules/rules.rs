@@ -0,0 +1,68 @@
+//! Evaluation of the rules of drones
+
+use std::fmt::{Display, Formatter};
+
+use crate::models::{DroneRule, DroneStatsInsert, RuleField, RuleOperator};
+
+impl RuleField {
+    /// Retrieve the value this field refers to from the reported stats
+    ///
+    /// Returns `None` if the value was not reported, e.g. a hook that isn't configured.
+    pub(crate) fn value(&self, stats: &DroneStatsInsert) -> Option<i64> {
+        match self {
+            RuleField::PreHookDuration => stats.pre_hook_duration,
+            RuleField::PostHookDuration => stats.post_hook_duration,
+            RuleField::CreateDuration => Some(stats.create_duration),
+            RuleField::CompleteDuration => Some(stats.complete_duration),
+            RuleField::OriginalSize => Some(stats.original_size),
+            RuleField::CompressedSize => Some(stats.compressed_size),
+            RuleField::DeduplicatedSize => Some(stats.deduplicated_size),
+            RuleField::Nfiles => Some(stats.nfiles),
+        }
+    }
+}
+
+impl Display for RuleField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleField::PreHookDuration => write!(f, "pre_hook_duration"),
+            RuleField::PostHookDuration => write!(f, "post_hook_duration"),
+            RuleField::CreateDuration => write!(f, "create_duration"),
+            RuleField::CompleteDuration => write!(f, "complete_duration"),
+            RuleField::OriginalSize => write!(f, "original_size"),
+            RuleField::CompressedSize => write!(f, "compressed_size"),
+            RuleField::DeduplicatedSize => write!(f, "deduplicated_size"),
+            RuleField::Nfiles => write!(f, "nfiles"),
+        }
+    }
+}
+
+impl RuleOperator {
+    /// Check whether the reported value satisfies the comparison with the configured value
+    pub fn is_satisfied(&self, reported: i64, configured: i64) -> bool {
+        match self {
+            RuleOperator::Less => reported < configured,
+            RuleOperator::LessOrEquals => reported <= configured,
+            RuleOperator::Greater => reported > configured,
+            RuleOperator::GreaterOrEquals => reported >= configured,
+        }
+    }
+}
+
+impl Display for RuleOperator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleOperator::Less => write!(f, "<"),
+            RuleOperator::LessOrEquals => write!(f, "<="),
+            RuleOperator::Greater => write!(f, ">"),
+            RuleOperator::GreaterOrEquals => write!(f, ">="),
+        }
+    }
+}
+
+/// Retrieve the value of the reported stats if they violate the rule
+pub(crate) fn check_rule(rule: &DroneRule, stats: &DroneStatsInsert) -> Option<i64> {
+    rule.field
+        .value(stats)
+        .filter(|value| !rule.operator.is_satisfied(*value, rule.value))
+}
@@ -0,0 +1,383 @@
+//! Helper to work with the raw and aggregated stats of drones
+
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Timelike};
+use common::RepositoryTotals;
+
+use uuid::Uuid;
+
+use crate::models::{AggregationPeriod, DroneStatsAggregate, DroneStatsSample};
+
+/// Retrieve the start of the hour the given point in time belongs to
+pub fn start_of_hour(time: NaiveDateTime) -> NaiveDateTime {
+    time.date().and_hms_opt(time.hour(), 0, 0).unwrap()
+}
+
+/// Retrieve the start of the day the given point in time belongs to
+pub fn start_of_day(time: NaiveDateTime) -> NaiveDateTime {
+    time.date().and_time(NaiveTime::MIN)
+}
+
+/// Retrieve the start of the week (monday) the given point in time belongs to
+pub fn start_of_week(time: NaiveDateTime) -> NaiveDateTime {
+    start_of_day(time) - Duration::days(time.weekday().num_days_from_monday() as i64)
+}
+
+impl AggregationPeriod {
+    /// Retrieve the start of the period the given point in time belongs to
+    pub fn start(&self, time: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            AggregationPeriod::Day => start_of_day(time),
+            AggregationPeriod::Week => start_of_week(time),
+        }
+    }
+}
+
+/// A single stats sample of a drone.
+///
+/// The sample is either a raw [DroneStats](crate::models::DroneStats) record
+/// or a [DroneStatsAggregate].
+pub struct StatsSample {
+    /// The primary key of the record
+    pub uuid: Uuid,
+    /// The point in time the stats were collected, corrected by the clock skew of the drone,
+    /// or the start of the aggregated period
+    pub created_at: NaiveDateTime,
+    /// The period the sample was aggregated over, if it is an aggregate
+    pub period: Option<AggregationPeriod>,
+    /// The number of stats this sample represents
+    pub count: i64,
+    /// The duration in seconds that the pre hook took to execute
+    pub pre_hook_duration: Option<i64>,
+    /// The duration in seconds that the post hook took to execute
+    pub post_hook_duration: Option<i64>,
+    /// The duration in seconds that the archive creation took
+    pub create_duration: i64,
+    /// The duration in seconds that the complete operation took
+    pub complete_duration: i64,
+    /// Original file size in bytes
+    pub original_size: i64,
+    /// Compressed file size in bytes
+    pub compressed_size: i64,
+    /// Deduplicated file size in bytes
+    pub deduplicated_size: i64,
+    /// Number of archived files
+    pub nfiles: i64,
+    /// Whether the stats deviated from their baseline, always `false` for aggregates
+    pub anomaly: bool,
+    /// The number of warnings borg logged, always `0` for aggregates
+    pub warnings: i64,
+    /// The number of added files, never set for aggregates
+    pub files_added: Option<i64>,
+    /// The number of modified files, never set for aggregates
+    pub files_modified: Option<i64>,
+    /// The number of unchanged files, never set for aggregates
+    pub files_unchanged: Option<i64>,
+    /// The changed paths as json, only set if they were queried explicitly
+    pub changed_paths: Option<String>,
+    /// Whether `changed_paths` is incomplete
+    pub changed_paths_truncated: bool,
+    /// The name of the created archive, never set for aggregates
+    pub archive_name: Option<String>,
+    /// The id of the created archive, never set for aggregates
+    pub archive_id: Option<String>,
+    /// The point in time borg started to create the archive, never set for aggregates
+    pub archive_start: Option<NaiveDateTime>,
+    /// The point in time borg finished the archive, never set for aggregates
+    pub archive_end: Option<NaiveDateTime>,
+    /// The totals of the repository after the archive was created, never set for aggregates
+    pub repository: Option<RepositoryTotals>,
+    /// The point in time the drone started the run, never set for aggregates
+    pub started_at: Option<NaiveDateTime>,
+    /// The point in time the drone finished the run, never set for aggregates
+    pub finished_at: Option<NaiveDateTime>,
+    /// The point in time the pre hook was started, never set for aggregates
+    pub pre_hook_started_at: Option<NaiveDateTime>,
+    /// The point in time the pre hook finished, never set for aggregates
+    pub pre_hook_finished_at: Option<NaiveDateTime>,
+    /// The point in time the post hook was started, never set for aggregates
+    pub post_hook_started_at: Option<NaiveDateTime>,
+    /// The point in time the post hook finished, never set for aggregates
+    pub post_hook_finished_at: Option<NaiveDateTime>,
+    /// The skew of the clock of the drone in seconds, never set for aggregates
+    pub clock_skew: Option<i64>,
+    /// The point in time the stats were received, never set for aggregates
+    pub received_at: Option<NaiveDateTime>,
+    /// The accumulated values this sample represents
+    pub accumulated: StatsAccumulator,
+}
+
+/// Retrieve the totals of the repository from stats, if the drone reported them
+fn repository_totals(stats: &DroneStatsSample) -> Option<RepositoryTotals> {
+    Some(RepositoryTotals {
+        total_chunks: stats.repository_total_chunks? as u64,
+        total_csize: stats.repository_total_csize? as u64,
+        total_size: stats.repository_total_size? as u64,
+        total_unique_chunks: stats.repository_total_unique_chunks? as u64,
+        unique_csize: stats.repository_unique_csize? as u64,
+        unique_size: stats.repository_unique_size? as u64,
+    })
+}
+
+impl From<DroneStatsSample> for StatsSample {
+    fn from(value: DroneStatsSample) -> Self {
+        let repository = repository_totals(&value);
+        let accumulated = StatsAccumulator {
+            count: 1,
+            pre_hook_duration: value.pre_hook_duration.map(|x| ValueAccumulator::new(x, 1)),
+            post_hook_duration: value
+                .post_hook_duration
+                .map(|x| ValueAccumulator::new(x, 1)),
+            create_duration: ValueAccumulator::new(value.create_duration, 1),
+            complete_duration: ValueAccumulator::new(value.complete_duration, 1),
+            original_size: ValueAccumulator::new(value.original_size, 1),
+            compressed_size: ValueAccumulator::new(value.compressed_size, 1),
+            deduplicated_size: ValueAccumulator::new(value.deduplicated_size, 1),
+            nfiles: ValueAccumulator::new(value.nfiles, 1),
+        };
+
+        Self {
+            uuid: value.uuid,
+            created_at: value.collected_at.unwrap_or(value.created_at),
+            period: None,
+            count: 1,
+            pre_hook_duration: value.pre_hook_duration,
+            post_hook_duration: value.post_hook_duration,
+            create_duration: value.create_duration,
+            complete_duration: value.complete_duration,
+            original_size: value.original_size,
+            compressed_size: value.compressed_size,
+            deduplicated_size: value.deduplicated_size,
+            nfiles: value.nfiles,
+            anomaly: value.anomaly,
+            warnings: value.warnings,
+            files_added: value.files_added,
+            files_modified: value.files_modified,
+            files_unchanged: value.files_unchanged,
+            changed_paths: None,
+            changed_paths_truncated: value.changed_paths_truncated,
+            archive_name: value.archive_name,
+            archive_id: value.archive_id,
+            archive_start: value.archive_start,
+            archive_end: value.archive_end,
+            repository,
+            started_at: value.started_at,
+            finished_at: value.finished_at,
+            pre_hook_started_at: value.pre_hook_started_at,
+            pre_hook_finished_at: value.pre_hook_finished_at,
+            post_hook_started_at: value.post_hook_started_at,
+            post_hook_finished_at: value.post_hook_finished_at,
+            clock_skew: value.clock_skew,
+            received_at: Some(value.created_at),
+            accumulated,
+        }
+    }
+}
+
+impl From<DroneStatsAggregate> for StatsSample {
+    fn from(value: DroneStatsAggregate) -> Self {
+        let count = value.count;
+        let accumulated = StatsAccumulator {
+            count,
+            pre_hook_duration: value.pre_hook_duration.map(|x| {
+                ValueAccumulator::aggregated(
+                    x,
+                    value.pre_hook_duration_min,
+                    value.pre_hook_duration_max,
+                    value.pre_hook_count.unwrap_or(count),
+                )
+            }),
+            post_hook_duration: value.post_hook_duration.map(|x| {
+                ValueAccumulator::aggregated(
+                    x,
+                    value.post_hook_duration_min,
+                    value.post_hook_duration_max,
+                    value.post_hook_count.unwrap_or(count),
+                )
+            }),
+            create_duration: ValueAccumulator::aggregated(
+                value.create_duration,
+                value.create_duration_min,
+                value.create_duration_max,
+                count,
+            ),
+            complete_duration: ValueAccumulator::aggregated(
+                value.complete_duration,
+                value.complete_duration_min,
+                value.complete_duration_max,
+                count,
+            ),
+            original_size: ValueAccumulator::aggregated(
+                value.original_size,
+                value.original_size_min,
+                value.original_size_max,
+                count,
+            ),
+            compressed_size: ValueAccumulator::aggregated(
+                value.compressed_size,
+                value.compressed_size_min,
+                value.compressed_size_max,
+                count,
+            ),
+            deduplicated_size: ValueAccumulator::aggregated(
+                value.deduplicated_size,
+                value.deduplicated_size_min,
+                value.deduplicated_size_max,
+                count,
+            ),
+            nfiles: ValueAccumulator::aggregated(
+                value.nfiles,
+                value.nfiles_min,
+                value.nfiles_max,
+                count,
+            ),
+        };
+
+        Self {
+            uuid: value.uuid,
+            created_at: value.created_at,
+            period: Some(value.period),
+            count: value.count,
+            pre_hook_duration: value.pre_hook_duration,
+            post_hook_duration: value.post_hook_duration,
+            create_duration: value.create_duration,
+            complete_duration: value.complete_duration,
+            original_size: value.original_size,
+            compressed_size: value.compressed_size,
+            deduplicated_size: value.deduplicated_size,
+            nfiles: value.nfiles,
+            anomaly: false,
+            warnings: 0,
+            files_added: None,
+            files_modified: None,
+            files_unchanged: None,
+            changed_paths: None,
+            changed_paths_truncated: false,
+            archive_name: None,
+            archive_id: None,
+            archive_start: None,
+            archive_end: None,
+            repository: None,
+            started_at: None,
+            finished_at: None,
+            pre_hook_started_at: None,
+            pre_hook_finished_at: None,
+            post_hook_started_at: None,
+            post_hook_finished_at: None,
+            clock_skew: None,
+            received_at: None,
+            accumulated,
+        }
+    }
+}
+
+/// Accumulates the minimum, sum and maximum of a value
+#[derive(Copy, Clone)]
+pub struct ValueAccumulator {
+    /// The number of values that were accumulated
+    pub count: i64,
+    /// The minimum of all values
+    pub min: i64,
+    /// The sum of all values
+    pub sum: i128,
+    /// The maximum of all values
+    pub max: i64,
+}
+
+impl ValueAccumulator {
+    /// Start accumulating with a value that occurred `weight` times
+    pub fn new(value: i64, weight: i64) -> Self {
+        Self {
+            count: weight,
+            min: value,
+            sum: value as i128 * weight as i128,
+            max: value,
+        }
+    }
+
+    /// Restore the accumulator of an aggregated value from its average, minimum and maximum
+    ///
+    /// If the minimum or maximum weren't recorded, the average is used instead.
+    pub fn aggregated(avg: i64, min: Option<i64>, max: Option<i64>, count: i64) -> Self {
+        Self {
+            count,
+            min: min.unwrap_or(avg),
+            sum: avg as i128 * count as i128,
+            max: max.unwrap_or(avg),
+        }
+    }
+
+    /// Merge another accumulator into this one
+    pub fn merge(&mut self, other: &Self) {
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.sum += other.sum;
+        self.max = self.max.max(other.max);
+    }
+
+    /// Merge an optional accumulator into another optional accumulator
+    pub fn merge_optional(this: &mut Option<Self>, other: Option<&Self>) {
+        if let Some(other) = other {
+            match this {
+                Some(acc) => acc.merge(other),
+                None => *this = Some(*other),
+            }
+        }
+    }
+
+    /// The average of all accumulated values
+    pub fn avg(&self) -> f64 {
+        self.sum as f64 / self.count as f64
+    }
+}
+
+/// Accumulates multiple [StatsSample]s
+///
+/// Aggregated samples contribute the number of stats they represent to every value.
+#[derive(Copy, Clone)]
+pub struct StatsAccumulator {
+    /// The number of accumulated stats
+    pub count: i64,
+    /// The accumulated pre hook durations
+    pub pre_hook_duration: Option<ValueAccumulator>,
+    /// The accumulated post hook durations
+    pub post_hook_duration: Option<ValueAccumulator>,
+    /// The accumulated create durations
+    pub create_duration: ValueAccumulator,
+    /// The accumulated complete durations
+    pub complete_duration: ValueAccumulator,
+    /// The accumulated original sizes
+    pub original_size: ValueAccumulator,
+    /// The accumulated compressed sizes
+    pub compressed_size: ValueAccumulator,
+    /// The accumulated deduplicated sizes
+    pub deduplicated_size: ValueAccumulator,
+    /// The accumulated number of files
+    pub nfiles: ValueAccumulator,
+}
+
+impl StatsAccumulator {
+    /// Start accumulating with the given sample
+    pub fn new(sample: &StatsSample) -> Self {
+        sample.accumulated
+    }
+
+    /// Add a sample
+    pub fn add(&mut self, sample: &StatsSample) {
+        let other = &sample.accumulated;
+        self.count += other.count;
+        ValueAccumulator::merge_optional(
+            &mut self.pre_hook_duration,
+            other.pre_hook_duration.as_ref(),
+        );
+        ValueAccumulator::merge_optional(
+            &mut self.post_hook_duration,
+            other.post_hook_duration.as_ref(),
+        );
+        self.create_duration.merge(&other.create_duration);
+        self.complete_duration.merge(&other.complete_duration);
+        self.original_size.merge(&other.original_size);
+        self.compressed_size.merge(&other.compressed_size);
+        self.deduplicated_size.merge(&other.deduplicated_size);
+        self.nfiles.merge(&other.nfiles);
+    }
+}
@@ -13,7 +13,6 @@ use actix_web::web::{scope, Data, JsonConfig, PayloadConfig};
 use actix_web::{App, HttpServer};
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
-use borgbackup::common::CommonOptions;
 use log::info;
 use rorm::Database;
 use utoipa::OpenApi;
@@ -21,12 +20,20 @@ use utoipa_swagger_ui::{SwaggerUi, Url};
 
 use crate::chan::MatrixNotifierChan;
 use crate::config::Config;
-use crate::handler::api::{error, stats};
+use crate::handler::api::{error, prune, prune_policy, restore, stats};
 use crate::handler::frontend::{
-    create_drone, delete_drone, get_all_drones, get_drone, get_drone_stats, get_key, login, logout,
-    test,
+    activate_drone, create_drone, create_rule, deactivate_drone, delete_check_policy, delete_drone,
+    delete_prune_policy, delete_rule, download_archive_file, export_archive_tar,
+    get_aggregated_drone_stats, get_all_drones, get_all_rules, get_archive_contents, get_archives,
+    get_check_policy, get_checks, get_drills, get_drone, get_drone_restores, get_drone_stats,
+    get_forecast, get_inventory, get_key, get_prune_policy, get_prunes, get_repository,
+    get_rule_violations, get_warnings, login, logout, set_check_policy, set_prune_policy, test,
+    update_drone,
 };
+use crate::handler::metrics::metrics;
 use crate::middleware::{handle_not_found, json_extractor_error, AuthenticationRequired};
+use crate::modules::archives::ArchiveCache;
+use crate::modules::borg::common_options;
 use crate::swagger::{ApiDoc, FrontendDoc};
 
 /// Start the server
@@ -45,15 +52,10 @@ pub async fn start_server(
     )
     .map_err(|_| "Invalid SecretKey. Generate one using the keygen subcommand".to_string())?;
 
-    let common_options = CommonOptions {
-        local_path: Some(config.borg.borg_path.clone()),
-        remote_path: config.borg.borg_remote_path.clone(),
-        upload_ratelimit: None,
-        rsh: Some(format!(
-            "ssh -i {} -o 'StrictHostKeyChecking accept-new'",
-            shlex::quote(&config.borg.ssh_key_path)
-        )),
-    };
+    let common_options = common_options(&config.borg);
+    let archive_cache = ArchiveCache::new(std::time::Duration::from_secs(
+        config.borg.archive_cache_minutes as u64 * 60,
+    ));
 
     let s_addr = SocketAddr::new(config.server.listen_address, config.server.listen_port);
     info!("Starting to listen on {}", s_addr);
@@ -66,6 +68,7 @@ pub async fn start_server(
             .app_data(Data::new(db.clone()))
             .app_data(Data::new(common_options.clone()))
             .app_data(Data::new(matrix_notifier_chan.clone()))
+            .app_data(Data::new(archive_cache.clone()))
             .app_data(conf_data.clone())
             .wrap(setup_logging_mw(LoggingMiddlewareConfig::default()))
             .wrap(Compress::default())
@@ -101,10 +104,44 @@ pub async fn start_server(
                     .service(create_drone)
                     .service(get_all_drones)
                     .service(get_drone)
+                    .service(update_drone)
                     .service(delete_drone)
-                    .service(get_drone_stats),
+                    .service(activate_drone)
+                    .service(deactivate_drone)
+                    .service(get_drone_stats)
+                    .service(get_aggregated_drone_stats)
+                    .service(get_forecast)
+                    .service(get_repository)
+                    .service(get_archives)
+                    .service(get_archive_contents)
+                    .service(download_archive_file)
+                    .service(export_archive_tar)
+                    .service(get_drills)
+                    .service(get_drone_restores)
+                    .service(get_check_policy)
+                    .service(set_check_policy)
+                    .service(delete_check_policy)
+                    .service(get_checks)
+                    .service(get_prune_policy)
+                    .service(set_prune_policy)
+                    .service(delete_prune_policy)
+                    .service(get_prunes)
+                    .service(get_warnings)
+                    .service(get_inventory)
+                    .service(get_rule_violations)
+                    .service(create_rule)
+                    .service(get_all_rules)
+                    .service(delete_rule),
             )
-            .service(scope("/api/drone/v1").service(stats).service(error))
+            .service(
+                scope("/api/drone/v1")
+                    .service(stats)
+                    .service(error)
+                    .service(restore)
+                    .service(prune_policy)
+                    .service(prune),
+            )
+            .service(metrics)
     })
     .bind((config.server.listen_address, config.server.listen_port))
     .map_err(|e| e.to_string())?
@@ -3,6 +3,7 @@ use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder
 use utoipa::{Modify, OpenApi};
 
 use crate::handler::{api, frontend, ApiErrorResponse, ApiStatusCode};
+use crate::models;
 
 struct TokenSecurity;
 
@@ -28,15 +29,31 @@ impl Modify for TokenSecurity {
 /// Helper struct for the drone api openapi definitions.
 #[derive(OpenApi)]
 #[openapi(
-    paths(api::stats, api::error),
+    paths(
+        api::stats,
+        api::error,
+        api::restore,
+        api::prune_policy,
+        api::prune
+    ),
     components(schemas(
         ApiErrorResponse,
         ApiStatusCode,
         StatReport,
+        BorgWarning,
+        ChangedFiles,
+        ChangedPath,
+        FileChange,
         CreateStats,
+        RepositoryTotals,
         HookStats,
         ErrorReport,
-        State
+        State,
+        RestoreReport,
+        PrunePolicy,
+        GetPrunePolicyResponse,
+        PruneStats,
+        HostInventory
     )),
     modifiers(&TokenSecurity)
 )]
@@ -65,9 +82,35 @@ impl Modify for CookieSecurity {
         frontend::create_drone,
         frontend::get_all_drones,
         frontend::get_drone,
+        frontend::update_drone,
         frontend::delete_drone,
+        frontend::activate_drone,
+        frontend::deactivate_drone,
         frontend::get_key,
-        frontend::get_drone_stats
+        frontend::get_drone_stats,
+        frontend::get_aggregated_drone_stats,
+        frontend::get_forecast,
+        frontend::get_repository,
+        frontend::get_archives,
+        frontend::get_archive_contents,
+        frontend::download_archive_file,
+        frontend::export_archive_tar,
+        frontend::get_drills,
+        frontend::get_drone_restores,
+        frontend::get_check_policy,
+        frontend::set_check_policy,
+        frontend::delete_check_policy,
+        frontend::get_checks,
+        frontend::get_prune_policy,
+        frontend::set_prune_policy,
+        frontend::delete_prune_policy,
+        frontend::get_prunes,
+        frontend::get_warnings,
+        frontend::get_inventory,
+        frontend::create_rule,
+        frontend::get_all_rules,
+        frontend::delete_rule,
+        frontend::get_rule_violations,
     ),
     components(schemas(
         ApiErrorResponse,
@@ -77,9 +120,50 @@ impl Modify for CookieSecurity {
         frontend::CreateDroneResponse,
         frontend::GetAllDronesResponse,
         frontend::GetDroneResponse,
+        frontend::UpdateDroneRequest,
         frontend::GetKeyResponse,
         frontend::GetDroneStats,
         frontend::DroneStat,
+        RepositoryTotals,
+        ChangedPath,
+        FileChange,
+        models::AggregationPeriod,
+        frontend::StatsBucketSize,
+        frontend::AggregatedValue,
+        frontend::DroneStatBucket,
+        frontend::GetAggregatedDroneStats,
+        frontend::GetForecastResponse,
+        frontend::RepositorySnapshotResponse,
+        frontend::GetRepositoryResponse,
+        frontend::Archive,
+        frontend::GetArchivesResponse,
+        frontend::ArchiveContentItem,
+        frontend::GetArchiveContentsResponse,
+        frontend::RestoreDrillResponse,
+        frontend::GetDrillsResponse,
+        frontend::DroneRestoreResponse,
+        frontend::GetDroneRestoresResponse,
+        frontend::CheckPolicy,
+        frontend::GetCheckPolicyResponse,
+        frontend::RepositoryCheck,
+        frontend::GetChecksResponse,
+        PrunePolicy,
+        GetPrunePolicyResponse,
+        frontend::RepositoryPrune,
+        frontend::GetPrunesResponse,
+        frontend::DroneWarningResponse,
+        frontend::GetWarningsResponse,
+        frontend::DroneInventoryResponse,
+        frontend::GetInventoryResponse,
+        models::CheckMode,
+        frontend::CreateRuleRequest,
+        frontend::CreateRuleResponse,
+        frontend::GetRuleResponse,
+        frontend::GetAllRulesResponse,
+        frontend::RuleViolation,
+        frontend::GetRuleViolationsResponse,
+        models::RuleField,
+        models::RuleOperator,
     )),
     modifiers(&CookieSecurity)
 )]
@@ -0,0 +1,132 @@
+//! The task that executes the scheduled integrity checks of the repositories
+
+use std::time::{Duration, Instant};
+
+use borgbackup::common::CommonOptions;
+use chrono::Utc;
+use common::borg::check_repository;
+use log::{error, info, warn};
+use rorm::fields::ForeignModelByField;
+use rorm::{and, insert, query, Database, Model};
+use uuid::Uuid;
+
+use crate::chan::{MatrixNotifierChan, Notification};
+use crate::models::{Drone, DroneCheck, DroneCheckInsert, DroneCheckPolicy};
+
+/// The interval the check policies are evaluated in
+const CHECK_SCHEDULE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The maximum length of the output that is stored
+const MAX_OUTPUT_LENGTH: usize = 65535;
+
+/// Start the task that checks the repositories according to their check policies
+pub(crate) fn start_check_task(
+    db: Database,
+    common_options: CommonOptions,
+    matrix: MatrixNotifierChan,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_SCHEDULE_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(err) = run_due_checks(&db, &common_options, &matrix).await {
+                error!("Error while checking repositories: {err}");
+            }
+        }
+    });
+}
+
+async fn run_due_checks(
+    db: &Database,
+    common_options: &CommonOptions,
+    matrix: &MatrixNotifierChan,
+) -> Result<(), rorm::Error> {
+    let policies = query!(db, DroneCheckPolicy).all().await?;
+
+    for policy in policies {
+        let Some(drone) = query!(db, Drone)
+            .condition(and!(
+                Drone::F.uuid.equals(policy.drone.key().as_ref()),
+                Drone::F.active.equals(true),
+                Drone::F.archived_at.is_null()
+            ))
+            .optional()
+            .await?
+        else {
+            continue;
+        };
+
+        let last_check = query!(db, (DroneCheck::F.created_at,))
+            .condition(DroneCheck::F.drone.equals(drone.uuid.as_ref()))
+            .order_desc(DroneCheck::F.created_at)
+            .optional()
+            .await?;
+        if let Some((last_check,)) = last_check {
+            // Policies with an interval beyond the representable range are never due again
+            let due = last_check.checked_add_signed(chrono::Duration::hours(policy.interval_hours));
+            if !due.is_some_and(|due| Utc::now().naive_utc() >= due) {
+                continue;
+            }
+        }
+
+        info!(
+            "Checking repository of drone {name} ({mode:?})",
+            name = drone.name,
+            mode = policy.mode
+        );
+
+        let start = Instant::now();
+        // The output is only set, if the check failed
+        let (success, output) = match check_repository(
+            common_options,
+            &drone.repository,
+            &drone.passphrase,
+            policy.mode.into(),
+            // Partial checks are validated to be positive
+            policy.max_duration.map(|x| x as u64),
+        )
+        .await
+        {
+            Ok(res) if res.success => (true, None),
+            Ok(res) if res.problems.is_empty() => {
+                (false, Some("borg check failed without output".to_string()))
+            }
+            Ok(res) => (false, Some(res.problems.join("\n"))),
+            Err(err) => (false, Some(err.to_string())),
+        };
+        let duration = start.elapsed();
+
+        insert!(db, DroneCheckInsert)
+            .return_nothing()
+            .single(&DroneCheckInsert {
+                uuid: Uuid::new_v4(),
+                drone: ForeignModelByField::Key(drone.uuid),
+                mode: policy.mode,
+                partial: policy.max_duration.is_some(),
+                success,
+                duration: duration.as_secs() as i64,
+                output: output
+                    .as_ref()
+                    .map(|x| x.chars().take(MAX_OUTPUT_LENGTH).collect()),
+            })
+            .await?;
+
+        if let Some(output) = output {
+            warn!(
+                "Check of repository of drone {name} failed: {output}",
+                name = drone.name
+            );
+
+            if let Err(err) = matrix
+                .send(Notification::CheckFailed { drone, output })
+                .await
+            {
+                warn!("Error while sending to matrix notifier chan: {err}");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,277 @@
+//! The task that periodically verifies that the backups of the drones can be restored
+
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use borgbackup::common::CommonOptions;
+use chrono::Utc;
+use common::borg::BorgError;
+use log::{error, info, warn};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use rorm::fields::ForeignModelByField;
+use rorm::{and, insert, query, Database, Model};
+use uuid::Uuid;
+
+use crate::chan::{MatrixNotifierChan, Notification};
+use crate::config::{Config, DrillConfig};
+use crate::models::{Drone, RestoreDrill, RestoreDrillInsert};
+use crate::modules::borg::{extract_paths, list_archives, sample_archive_files};
+
+/// The interval the drones are checked for due drills in
+const DRILL_SCHEDULE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The maximum length of an error that is stored
+const MAX_ERROR_LENGTH: usize = 4096;
+
+/// Start the task that periodically executes restore drills for all active drones
+///
+/// A drill of a drone is due, once the configured interval has passed since its last drill.
+/// If no drills are configured, the task is not started.
+pub(crate) fn start_drill_task(
+    config: &Config,
+    db: Database,
+    common_options: CommonOptions,
+    matrix: MatrixNotifierChan,
+) {
+    let Some(drill_config) = config.drill.clone() else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DRILL_SCHEDULE_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(err) = run_drills(&drill_config, &db, &common_options, &matrix).await {
+                error!("Error while executing restore drills: {err}");
+            }
+        }
+    });
+}
+
+async fn run_drills(
+    config: &DrillConfig,
+    db: &Database,
+    common_options: &CommonOptions,
+    matrix: &MatrixNotifierChan,
+) -> Result<(), rorm::Error> {
+    let drones = query!(db, Drone)
+        .condition(and!(
+            Drone::F.active.equals(true),
+            Drone::F.archived_at.is_null()
+        ))
+        .all()
+        .await?;
+
+    for drone in drones {
+        let last_drill = query!(db, (RestoreDrill::F.created_at,))
+            .condition(RestoreDrill::F.drone.equals(drone.uuid.as_ref()))
+            .order_desc(RestoreDrill::F.created_at)
+            .optional()
+            .await?;
+        if let Some((last_drill,)) = last_drill {
+            let due = last_drill
+                .checked_add_signed(chrono::Duration::hours(config.interval_hours as i64));
+            if !due.is_some_and(|due| Utc::now().naive_utc() >= due) {
+                continue;
+            }
+        }
+
+        let result = run_drill(config, common_options, &drone).await;
+
+        insert!(db, RestoreDrillInsert)
+            .return_nothing()
+            .single(&RestoreDrillInsert {
+                uuid: Uuid::new_v4(),
+                drone: ForeignModelByField::Key(drone.uuid),
+                archive: result.archive.clone(),
+                success: result.error.is_none(),
+                files: result.files as i64,
+                bytes_restored: result.bytes_restored as i64,
+                duration_ms: result.duration.as_millis() as i64,
+                error: result
+                    .error
+                    .as_ref()
+                    .map(|x| x.chars().take(MAX_ERROR_LENGTH).collect()),
+            })
+            .await?;
+
+        match result.error {
+            None => info!(
+                "Restore drill of drone {name} restored {files} files ({bytes} bytes) in {duration:?}",
+                name = drone.name,
+                files = result.files,
+                bytes = result.bytes_restored,
+                duration = result.duration,
+            ),
+            Some(error) => {
+                warn!(
+                    "Restore drill of drone {name} failed: {error}",
+                    name = drone.name
+                );
+
+                if let Err(err) = matrix
+                    .send(Notification::RestoreDrillFailed {
+                        drone,
+                        archive: result.archive,
+                        error,
+                    })
+                    .await
+                {
+                    warn!("Error while sending to matrix notifier chan: {err}");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The result of a single restore drill
+#[derive(Default)]
+struct DrillResult {
+    archive: Option<String>,
+    files: usize,
+    bytes_restored: u64,
+    duration: Duration,
+    error: Option<String>,
+}
+
+/// The errors that can occur during a restore drill
+enum DrillError {
+    Borg(BorgError),
+    Io(io::Error),
+    NoArchives,
+    NoFiles,
+    SizeMismatch {
+        path: String,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl Display for DrillError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DrillError::Borg(err) => write!(f, "{err}"),
+            DrillError::Io(err) => write!(f, "IO error: {err}"),
+            DrillError::NoArchives => write!(f, "The repository contains no archives"),
+            DrillError::NoFiles => write!(f, "The archive contains no files"),
+            DrillError::SizeMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The restored file {path} has {actual} bytes, but {expected} bytes were expected"
+            ),
+        }
+    }
+}
+
+impl From<BorgError> for DrillError {
+    fn from(value: BorgError) -> Self {
+        Self::Borg(value)
+    }
+}
+
+impl From<io::Error> for DrillError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Restore random files of a random archive of the drone
+async fn run_drill(
+    config: &DrillConfig,
+    common_options: &CommonOptions,
+    drone: &Drone,
+) -> DrillResult {
+    let target_dir = Path::new(&config.scratch_dir).join(drone.uuid.to_string());
+
+    let mut result = DrillResult::default();
+    if let Err(err) =
+        restore_random_files(config, common_options, drone, &target_dir, &mut result).await
+    {
+        result.error = Some(err.to_string());
+    }
+
+    remove_dir(&target_dir).await;
+
+    result
+}
+
+async fn restore_random_files(
+    config: &DrillConfig,
+    common_options: &CommonOptions,
+    drone: &Drone,
+    target_dir: &Path,
+    result: &mut DrillResult,
+) -> Result<(), DrillError> {
+    let list = list_archives(common_options, &drone.repository, &drone.passphrase).await?;
+    let archive = list
+        .archives
+        .choose(&mut thread_rng())
+        .ok_or(DrillError::NoArchives)?
+        .name
+        .clone();
+    result.archive = Some(archive.clone());
+
+    let files = sample_archive_files(
+        common_options,
+        &drone.repository,
+        &archive,
+        &drone.passphrase,
+        config.files as usize,
+    )
+    .await?;
+    if files.is_empty() {
+        return Err(DrillError::NoFiles);
+    }
+
+    remove_dir(target_dir).await;
+    tokio::fs::create_dir_all(target_dir).await?;
+
+    let start = Instant::now();
+    extract_paths(
+        common_options,
+        &drone.repository,
+        &archive,
+        &drone.passphrase,
+        &files.iter().map(|x| x.path.as_str()).collect::<Vec<_>>(),
+        target_dir,
+    )
+    .await?;
+    result.duration = start.elapsed();
+
+    for file in files {
+        let actual = tokio::fs::metadata(target_dir.join(&file.path))
+            .await?
+            .len();
+        if actual != file.size {
+            return Err(DrillError::SizeMismatch {
+                path: file.path.clone(),
+                expected: file.size,
+                actual,
+            });
+        }
+
+        result.files += 1;
+        result.bytes_restored += actual;
+    }
+
+    Ok(())
+}
+
+/// Remove a directory with all its contents, if it exists
+async fn remove_dir(path: &Path) {
+    if let Err(err) = tokio::fs::remove_dir_all(path).await {
+        if err.kind() != io::ErrorKind::NotFound {
+            warn!("Could not remove {}: {err}", path.display());
+        }
+    }
+}
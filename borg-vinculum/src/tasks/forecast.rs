@@ -0,0 +1,120 @@
+//! The task that alerts about repositories that are about to reach their capacity limit
+//!
+//! Only changes are notified: an alert is sent once a repository is forecast to reach
+//! its limit within the configured number of days, not on every check.
+
+use std::time::Duration;
+
+use borgbackup::common::CommonOptions;
+use chrono::Utc;
+use common::borg::info_repository;
+use log::{error, warn};
+use rorm::{and, query, update, Database, Model};
+
+use crate::chan::{MatrixNotifierChan, Notification};
+use crate::config::Config;
+use crate::models::Drone;
+use crate::modules::forecast::{estimate_growth, Forecast};
+
+/// The interval the forecasts are checked in
+const FORECAST_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Start the task that periodically checks the forecasts of all repositories with a capacity limit
+pub(crate) fn start_forecast_task(
+    config: &Config,
+    db: Database,
+    common_options: CommonOptions,
+    matrix: MatrixNotifierChan,
+) {
+    let alert_days = config.forecast.alert_days;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FORECAST_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(err) = check_forecasts(alert_days, &db, &common_options, &matrix).await {
+                error!("Error while checking forecasts: {err}");
+            }
+        }
+    });
+}
+
+async fn check_forecasts(
+    alert_days: u32,
+    db: &Database,
+    common_options: &CommonOptions,
+    matrix: &MatrixNotifierChan,
+) -> Result<(), rorm::Error> {
+    let drones = query!(db, Drone)
+        .condition(and!(
+            Drone::F.active.equals(true),
+            Drone::F.archived_at.is_null(),
+            Drone::F.capacity_limit.is_not_null()
+        ))
+        .all()
+        .await?;
+
+    for drone in drones {
+        let mut tx = db.start_transaction().await?;
+        let growth = estimate_growth(&mut tx, &drone.uuid).await?;
+        tx.commit().await?;
+
+        let info = match info_repository(common_options, &drone.repository, &drone.passphrase).await
+        {
+            Ok(info) => info,
+            Err(err) => {
+                warn!(
+                    "Could not retrieve info of repository of drone {name}: {err}",
+                    name = drone.name
+                );
+                continue;
+            }
+        };
+
+        let now = Utc::now().naive_utc();
+        let forecast = Forecast::new(
+            info.cache.stats.unique_csize as i64,
+            growth,
+            drone.capacity_limit,
+            now,
+        );
+
+        let alert = forecast
+            .capacity_reached_at
+            .filter(|reached_at| (*reached_at - now).num_days() <= alert_days as i64);
+
+        if alert.is_some() != drone.capacity_alert {
+            update!(db, Drone)
+                .condition(Drone::F.uuid.equals(drone.uuid.as_ref()))
+                .set(Drone::F.capacity_alert, alert.is_some())
+                .exec()
+                .await?;
+        }
+
+        // Already notified by a previous check
+        if drone.capacity_alert {
+            continue;
+        }
+
+        let Some(reached_at) = alert else {
+            continue;
+        };
+
+        let days = (reached_at - now).num_days();
+        let message = format!(
+            "The repository is estimated to reach its capacity limit of {limit} bytes \
+            at {reached_at} (in {days} days).\nCurrent size: {size} bytes, growth: {growth:.0} bytes per day",
+            limit = drone.capacity_limit.unwrap_or_default(),
+            reached_at = reached_at.format("%Y-%m-%d %H:%M UTC"),
+            size = forecast.current_size,
+        );
+
+        if let Err(err) = matrix.send(Notification::Warning { drone, message }).await {
+            warn!("Error while sending to matrix notifier chan: {err}");
+        }
+    }
+
+    Ok(())
+}
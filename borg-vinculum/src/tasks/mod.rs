@@ -0,0 +1,7 @@
+//! The background tasks of borg-vinculum are defined here
+
+pub(crate) mod check;
+pub(crate) mod drill;
+pub(crate) mod forecast;
+pub(crate) mod repository;
+pub(crate) mod retention;
@@ -0,0 +1,82 @@
+//! The task that periodically collects information about the repositories of the drones
+
+use std::time::Duration;
+
+use borgbackup::common::CommonOptions;
+use common::borg::{info_repository, BorgError};
+use log::{error, info, warn};
+use rorm::fields::ForeignModelByField;
+use rorm::{insert, query, Database, Model};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::{Drone, RepositorySnapshotInsert};
+use crate::modules::borg::list_archives;
+
+/// Start the task that periodically takes snapshots of the repositories of all drones
+pub(crate) fn start_repository_task(config: &Config, db: Database, common_options: CommonOptions) {
+    let period = Duration::from_secs(config.borg.info_interval_hours as u64 * 60 * 60);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(err) = take_snapshots(&db, &common_options).await {
+                error!("Error while taking snapshots of repositories: {err}");
+            }
+        }
+    });
+}
+
+async fn take_snapshots(db: &Database, common_options: &CommonOptions) -> Result<(), rorm::Error> {
+    let drones = query!(db, Drone)
+        .condition(Drone::F.archived_at.is_null())
+        .all()
+        .await?;
+
+    for drone in drones {
+        let snapshot = match take_snapshot(&drone, common_options).await {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                warn!(
+                    "Could not take snapshot of repository of drone {name}: {err}",
+                    name = drone.name
+                );
+                continue;
+            }
+        };
+
+        insert!(db, RepositorySnapshotInsert)
+            .return_nothing()
+            .single(&snapshot)
+            .await?;
+    }
+
+    info!("Finished taking snapshots of repositories");
+
+    Ok(())
+}
+
+async fn take_snapshot(
+    drone: &Drone,
+    common_options: &CommonOptions,
+) -> Result<RepositorySnapshotInsert, BorgError> {
+    let info = info_repository(common_options, &drone.repository, &drone.passphrase).await?;
+    let list = list_archives(common_options, &drone.repository, &drone.passphrase).await?;
+
+    let stats = info.cache.stats;
+    Ok(RepositorySnapshotInsert {
+        uuid: Uuid::new_v4(),
+        drone: ForeignModelByField::Key(drone.uuid),
+        archives: list.archives.len() as i64,
+        total_chunks: stats.total_chunks as i64,
+        total_unique_chunks: stats.total_unique_chunks as i64,
+        total_size: stats.total_size as i64,
+        total_csize: stats.total_csize as i64,
+        unique_size: stats.unique_size as i64,
+        unique_csize: stats.unique_csize as i64,
+        last_modified: info.repository.last_modified,
+    })
+}
@@ -0,0 +1,234 @@
+//! The retention task of the stats of drones
+//!
+//! Raw [DroneStats] that are older than the configured number of days are rolled into
+//! daily [DroneStatsAggregate]s, daily aggregates are rolled into weekly aggregates later on.
+//! The [DroneWarning](crate::models::DroneWarning)s of rolled up stats are kept,
+//! they are just no longer linked to the stats.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, Utc};
+use log::{error, info};
+use rorm::fields::ForeignModelByField;
+use rorm::transaction::Transaction;
+use rorm::{and, insert, query, update, Database, Model};
+use tokio::time::interval;
+use uuid::Uuid;
+
+use crate::config::{Config, RetentionConfig};
+use crate::models::{
+    AggregationPeriod, Drone, DroneStats, DroneStatsAggregate, DroneStatsAggregateInsert,
+    DroneStatsSample,
+};
+use crate::modules::stats::{start_of_day, start_of_week, StatsAccumulator, StatsSample};
+
+/// The interval the retention is applied in
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Start the task that periodically applies the retention of drone stats
+pub(crate) fn start_retention_task(config: &Config, db: Database) {
+    let config = config.retention.clone();
+
+    tokio::spawn(async move {
+        let mut interval = interval(RETENTION_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(err) = apply_retention(&config, &db).await {
+                error!("Error while applying retention of drone stats: {err}");
+            }
+        }
+    });
+}
+
+/// Set the point in time stats were collected, if they were received before it was recorded
+///
+/// For these stats, the point in time they were received is used.
+/// This is executed on startup, as all stats received afterwards have the point in time set.
+pub(crate) async fn backfill_collected_at(db: &Database) -> Result<(), rorm::Error> {
+    let stats = query!(db, (DroneStats::F.uuid, DroneStats::F.created_at))
+        .condition(DroneStats::F.collected_at.is_null())
+        .all()
+        .await?;
+
+    if stats.is_empty() {
+        return Ok(());
+    }
+
+    let mut tx = db.start_transaction().await?;
+    for (uuid, created_at) in &stats {
+        update!(&mut tx, DroneStats)
+            .condition(DroneStats::F.uuid.equals(uuid.as_ref()))
+            .set(DroneStats::F.collected_at, Some(*created_at))
+            .exec()
+            .await?;
+    }
+    tx.commit().await?;
+
+    info!(
+        "Set the point in time of collection of {ct} stats",
+        ct = stats.len()
+    );
+
+    Ok(())
+}
+
+/// Roll old stats of all drones into aggregates
+async fn apply_retention(config: &RetentionConfig, db: &Database) -> Result<(), rorm::Error> {
+    let now = Utc::now().naive_utc();
+    let raw_cutoff = start_of_day(now - chrono::Duration::days(config.raw_days as i64));
+    let daily_cutoff = start_of_week(now - chrono::Duration::days(config.daily_days as i64));
+
+    let drones = query!(db, (Drone::F.uuid,)).all().await?;
+
+    for (drone,) in drones {
+        let mut tx = db.start_transaction().await?;
+
+        let raw: Vec<StatsSample> = query!(&mut tx, DroneStatsSample)
+            .condition(and!(
+                DroneStats::F.drone.equals(drone.as_ref()),
+                DroneStats::F.collected_at.less(raw_cutoff)
+            ))
+            .all()
+            .await?
+            .into_iter()
+            .map(StatsSample::from)
+            .collect();
+        let raw_ct = raw.len();
+
+        if !raw.is_empty() {
+            roll_up(&mut tx, drone, raw, AggregationPeriod::Day).await?;
+
+            rorm::delete!(&mut tx, DroneStats)
+                .condition(and!(
+                    DroneStats::F.drone.equals(drone.as_ref()),
+                    DroneStats::F.collected_at.less(raw_cutoff)
+                ))
+                .await?;
+        }
+
+        let daily: Vec<DroneStatsAggregate> = query!(&mut tx, DroneStatsAggregate)
+            .condition(and!(
+                DroneStatsAggregate::F.drone.equals(drone.as_ref()),
+                DroneStatsAggregate::F.period.equals(AggregationPeriod::Day),
+                DroneStatsAggregate::F.created_at.less(daily_cutoff)
+            ))
+            .all()
+            .await?;
+        let daily_ct = daily.len();
+
+        if !daily.is_empty() {
+            let uuids: Vec<Uuid> = daily.iter().map(|x| x.uuid).collect();
+            let samples = daily.into_iter().map(StatsSample::from).collect();
+            roll_up(&mut tx, drone, samples, AggregationPeriod::Week).await?;
+
+            for uuid in uuids {
+                rorm::delete!(&mut tx, DroneStatsAggregate)
+                    .condition(DroneStatsAggregate::F.uuid.equals(uuid.as_ref()))
+                    .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        if raw_ct > 0 || daily_ct > 0 {
+            info!(
+                "Retention of drone {drone}: rolled {raw_ct} stats into daily and \
+                {daily_ct} daily into weekly aggregates"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Aggregate the given samples of a drone per period.
+///
+/// Already existing aggregates of the same periods are merged and replaced.
+async fn roll_up(
+    tx: &mut Transaction,
+    drone: Uuid,
+    samples: Vec<StatsSample>,
+    period: AggregationPeriod,
+) -> Result<(), rorm::Error> {
+    let mut periods: BTreeMap<NaiveDateTime, StatsAccumulator> = BTreeMap::new();
+    for sample in &samples {
+        periods
+            .entry(period.start(sample.created_at))
+            .and_modify(|x| x.add(sample))
+            .or_insert_with(|| StatsAccumulator::new(sample));
+    }
+
+    let (Some(first), Some(last)) = (
+        periods.keys().next().copied(),
+        periods.keys().next_back().copied(),
+    ) else {
+        return Ok(());
+    };
+
+    let existing = query!(&mut *tx, DroneStatsAggregate)
+        .condition(and!(
+            DroneStatsAggregate::F.drone.equals(drone.as_ref()),
+            DroneStatsAggregate::F.period.equals(period),
+            DroneStatsAggregate::F.created_at.greater_or_equals(first),
+            DroneStatsAggregate::F.created_at.less_or_equals(last)
+        ))
+        .all()
+        .await?;
+
+    for aggregate in existing {
+        if let Some(acc) = periods.get_mut(&aggregate.created_at) {
+            let uuid = aggregate.uuid;
+            acc.add(&StatsSample::from(aggregate));
+
+            rorm::delete!(&mut *tx, DroneStatsAggregate)
+                .condition(DroneStatsAggregate::F.uuid.equals(uuid.as_ref()))
+                .await?;
+        }
+    }
+
+    let aggregates: Vec<DroneStatsAggregateInsert> = periods
+        .into_iter()
+        .map(|(start, acc)| DroneStatsAggregateInsert {
+            uuid: Uuid::new_v4(),
+            drone: ForeignModelByField::Key(drone),
+            period,
+            count: acc.count,
+            pre_hook_duration: acc.pre_hook_duration.map(|x| x.avg().round() as i64),
+            post_hook_duration: acc.post_hook_duration.map(|x| x.avg().round() as i64),
+            create_duration: acc.create_duration.avg().round() as i64,
+            complete_duration: acc.complete_duration.avg().round() as i64,
+            original_size: acc.original_size.avg().round() as i64,
+            compressed_size: acc.compressed_size.avg().round() as i64,
+            deduplicated_size: acc.deduplicated_size.avg().round() as i64,
+            nfiles: acc.nfiles.avg().round() as i64,
+            pre_hook_count: acc.pre_hook_duration.map(|x| x.count),
+            post_hook_count: acc.post_hook_duration.map(|x| x.count),
+            pre_hook_duration_min: acc.pre_hook_duration.map(|x| x.min),
+            pre_hook_duration_max: acc.pre_hook_duration.map(|x| x.max),
+            post_hook_duration_min: acc.post_hook_duration.map(|x| x.min),
+            post_hook_duration_max: acc.post_hook_duration.map(|x| x.max),
+            create_duration_min: Some(acc.create_duration.min),
+            create_duration_max: Some(acc.create_duration.max),
+            complete_duration_min: Some(acc.complete_duration.min),
+            complete_duration_max: Some(acc.complete_duration.max),
+            original_size_min: Some(acc.original_size.min),
+            original_size_max: Some(acc.original_size.max),
+            compressed_size_min: Some(acc.compressed_size.min),
+            compressed_size_max: Some(acc.compressed_size.max),
+            deduplicated_size_min: Some(acc.deduplicated_size.min),
+            deduplicated_size_max: Some(acc.deduplicated_size.max),
+            nfiles_min: Some(acc.nfiles.min),
+            nfiles_max: Some(acc.nfiles.max),
+            created_at: start,
+        })
+        .collect();
+
+    insert!(&mut *tx, DroneStatsAggregateInsert)
+        .bulk(&aggregates)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,276 @@
+//! Execution of the borg commands that are needed by both borg-drone and borg-vinculum,
+//! but are not covered by [borgbackup::asynchronous]
+
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::process::{ExitStatus, Output};
+
+use borgbackup::common::CommonOptions;
+use borgbackup::output::common::{Cache, Encryption, Repository};
+use borgbackup::output::logging::{LevelName, LoggingMessage};
+use chrono::NaiveDateTime;
+use log::{debug, trace};
+use serde::{Deserialize, Serialize};
+
+/// The errors that can occur while executing borg
+#[derive(Debug)]
+pub enum BorgError {
+    /// Error while splitting the arguments
+    ShlexError,
+    /// The command failed to execute
+    CommandFailed(io::Error),
+    /// Borg was terminated by a signal
+    TerminatedBySignal,
+    /// Borg exited with an error, the error messages of borg are attached
+    Failed(String),
+    /// Error while deserializing output of borg
+    DeserializeError(serde_json::Error),
+}
+
+impl Display for BorgError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BorgError::ShlexError => write!(f, "Error while splitting the arguments"),
+            BorgError::CommandFailed(err) => write!(f, "The command failed to execute: {err}"),
+            BorgError::TerminatedBySignal => write!(f, "Borg was terminated by a signal"),
+            BorgError::Failed(err) => write!(f, "Borg failed: {err}"),
+            BorgError::DeserializeError(err) => {
+                write!(f, "Error while deserializing borg output: {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BorgError {}
+
+impl From<serde_json::Error> for BorgError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::DeserializeError(value)
+    }
+}
+
+/// Build the command to execute borg with the given arguments.
+///
+/// `args` are the arguments after the common options, e.g. `info --json <repository>`.
+pub fn borg_command(
+    common_options: &CommonOptions,
+    passphrase: &str,
+    args: &str,
+) -> Result<tokio::process::Command, BorgError> {
+    let local_path = common_options.local_path.as_deref().unwrap_or("borg");
+
+    let args = format!("--log-json {common_options}{args}");
+    debug!("Calling borg: {local_path} {args}");
+    let args = shlex::split(&args).ok_or(BorgError::ShlexError)?;
+
+    let mut cmd = tokio::process::Command::new(local_path);
+    cmd.env("BORG_PASSPHRASE", passphrase).args(args);
+
+    Ok(cmd)
+}
+
+/// Execute borg with the given arguments and collect its output
+pub async fn execute_borg(
+    common_options: &CommonOptions,
+    passphrase: &str,
+    args: &str,
+) -> Result<Output, BorgError> {
+    borg_command(common_options, passphrase, args)?
+        .output()
+        .await
+        .map_err(BorgError::CommandFailed)
+}
+
+/// Check the exit code of borg and collect the error messages it logged
+pub fn check_output(res: &Output) -> Result<(), BorgError> {
+    check_status(res.status, &res.stderr)
+}
+
+/// Check the exit status of borg and collect the error messages it logged to `stderr`
+pub fn check_status(status: ExitStatus, stderr: &[u8]) -> Result<(), BorgError> {
+    let Some(exit_code) = status.code() else {
+        return Err(BorgError::TerminatedBySignal);
+    };
+
+    // An exit code of 1 are warnings
+    if exit_code > 1 {
+        return Err(BorgError::Failed(logged_problems(stderr, false).join("\n")));
+    }
+
+    Ok(())
+}
+
+/// Collect the errors borg logged to `stderr`, optionally including warnings
+///
+/// Lines that are not valid log messages are included as well.
+pub fn logged_problems(stderr: &[u8], include_warnings: bool) -> Vec<String> {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .filter_map(|line| {
+            trace!("borg output: {line}");
+            match serde_json::from_str(line) {
+                Ok(LoggingMessage::LogMessage {
+                    level_name,
+                    message,
+                    ..
+                }) => match level_name {
+                    LevelName::Error | LevelName::Critical => Some(message),
+                    LevelName::Warning if include_warnings => Some(message),
+                    _ => None,
+                },
+                Ok(_) => None,
+                Err(_) => Some(line.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Build the quoted archive specifier `<repository>::<archive>`
+pub fn archive_spec(repository: &str, archive: &str) -> String {
+    shlex::quote(&format!("{repository}::{archive}")).to_string()
+}
+
+/// Build the quoted path prefix pattern for a path in an archive
+pub fn path_pattern(path: &str) -> String {
+    shlex::quote(&format!("pp:{path}")).to_string()
+}
+
+/// The output of `borg info` for a repository
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RepositoryInfo {
+    /// Information about the repository
+    pub repository: Repository,
+    /// Information about the cache, this holds the stats of the repository
+    pub cache: Cache,
+    /// Information about the encryption of the repository
+    pub encryption: Option<Encryption>,
+}
+
+/// Retrieve the information about a repository
+pub async fn info_repository(
+    common_options: &CommonOptions,
+    repository: &str,
+    passphrase: &str,
+) -> Result<RepositoryInfo, BorgError> {
+    let res = execute_borg(
+        common_options,
+        passphrase,
+        &format!("info --json -- {}", shlex::quote(repository)),
+    )
+    .await?;
+
+    check_output(&res)?;
+
+    Ok(serde_json::from_slice(&res.stdout)?)
+}
+
+/// A single item in an archive, as returned by `borg list --json-lines`
+#[derive(Deserialize, Debug, Clone)]
+pub struct ArchiveItem {
+    /// The type of the item, e.g. `-` for regular files, `d` for directories
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The file mode, e.g. `-rw-r--r--`
+    pub mode: String,
+    /// The name of the owning user
+    pub user: String,
+    /// The name of the owning group
+    pub group: String,
+    /// The path of the item in the archive, without a leading slash
+    pub path: String,
+    /// The target of a link
+    #[serde(default)]
+    pub linktarget: String,
+    /// The modification time
+    pub mtime: NaiveDateTime,
+    /// The size in bytes
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// Retrieve the items of an archive
+///
+/// If `paths` is not empty, only the items at or below the given paths are returned.
+pub async fn list_archive_items(
+    common_options: &CommonOptions,
+    repository: &str,
+    archive: &str,
+    passphrase: &str,
+    paths: &[&str],
+) -> Result<Vec<ArchiveItem>, BorgError> {
+    let mut args = format!("list --json-lines -- {}", archive_spec(repository, archive));
+    for path in paths {
+        args.push(' ');
+        args.push_str(&path_pattern(path));
+    }
+
+    let res = execute_borg(common_options, passphrase, &args).await?;
+
+    check_output(&res)?;
+
+    res.stdout
+        .split(|x| *x == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| Ok(serde_json::from_slice(line)?))
+        .collect()
+}
+
+/// The extent of a `borg check`
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CheckMode {
+    /// Only check the consistency of the repository (`--repository-only`)
+    RepositoryOnly,
+    /// Check the consistency of the repository and the archives
+    Full,
+    /// Check the consistency of the repository and the archives and verify the integrity
+    /// of the data by reading it (`--verify-data`)
+    VerifyData,
+}
+
+/// The outcome of `borg check`
+#[derive(Serialize, Clone, Debug)]
+pub struct CheckOutput {
+    /// The extent of the check
+    pub mode: CheckMode,
+    /// Whether borg found no problems
+    pub success: bool,
+    /// The problems borg logged
+    pub problems: Vec<String>,
+}
+
+/// Check the integrity of a repository
+///
+/// If `max_duration` is set, only a partial check of the repository is executed,
+/// which is continued by the next check.
+/// This is only supported by [CheckMode::RepositoryOnly].
+pub async fn check_repository(
+    common_options: &CommonOptions,
+    repository: &str,
+    passphrase: &str,
+    mode: CheckMode,
+    max_duration: Option<u64>,
+) -> Result<CheckOutput, BorgError> {
+    let mut args = "check".to_string();
+    match mode {
+        CheckMode::RepositoryOnly => args.push_str(" --repository-only"),
+        CheckMode::Full => {}
+        CheckMode::VerifyData => args.push_str(" --verify-data"),
+    }
+    if let Some(max_duration) = max_duration {
+        args.push_str(&format!(" --max-duration {max_duration}"));
+    }
+    args.push_str(&format!(" -- {}", shlex::quote(repository)));
+
+    let res = execute_borg(common_options, passphrase, &args).await?;
+
+    // borg check exits with 1, if it found problems
+    let Some(exit_code) = res.status.code() else {
+        return Err(BorgError::TerminatedBySignal);
+    };
+
+    Ok(CheckOutput {
+        mode,
+        success: exit_code == 0,
+        problems: logged_problems(&res.stderr, true),
+    })
+}
@@ -7,9 +7,13 @@
 
 use std::fmt::{Display, Formatter};
 
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+#[cfg(feature = "borg")]
+pub mod borg;
+
 /// The state of the operation
 #[derive(Deserialize, Serialize, Copy, Clone, Debug, ToSchema)]
 pub enum State {
@@ -19,6 +23,10 @@ pub enum State {
     Create,
     /// Post hook
     PostHook,
+    /// Integrity check of the repository
+    Check,
+    /// Pruning of the archives in the repository
+    Prune,
 }
 
 impl Display for State {
@@ -27,6 +35,8 @@ impl Display for State {
             State::PreHook => write!(f, "pre hook"),
             State::PostHook => write!(f, "post hook"),
             State::Create => write!(f, "archive creation"),
+            State::Check => write!(f, "repository check"),
+            State::Prune => write!(f, "prune"),
         }
     }
 }
@@ -36,10 +46,37 @@ impl Display for State {
 pub struct HookStats {
     /// The duration of the pre hook operation in seconds
     pub duration: u64,
+    /// The point in time the hook was started, measured by the drone
+    #[serde(default)]
+    pub start: Option<DateTime<Utc>>,
+    /// The point in time the hook finished, measured by the drone
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>,
 }
 
-/// The stats of the creation of an archive
+/// The totals of the repository after the creation of an archive
+///
+/// The values are taken from the cache of borg.
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, ToSchema)]
+pub struct RepositoryTotals {
+    /// Number of chunks
+    pub total_chunks: u64,
+    /// Total compressed and encrypted size of all chunks multiplied with their reference counts
+    pub total_csize: u64,
+    /// Total uncompressed size of all chunks multiplied with their reference counts
+    pub total_size: u64,
+    /// Number of unique chunks
+    pub total_unique_chunks: u64,
+    /// Compressed and encrypted size of all chunks
+    pub unique_csize: u64,
+    /// Uncompressed size of all chunks
+    pub unique_size: u64,
+}
+
+/// The stats of the creation of an archive
+///
+/// The archive and repository values are not set by older drones.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct CreateStats {
     /// Original file size in bytes
     pub original_size: u64,
@@ -51,10 +88,103 @@ pub struct CreateStats {
     pub nfiles: u64,
     /// The duration of the create archive operation in seconds
     pub duration: u64,
+    /// The name of the created archive
+    #[serde(default)]
+    #[schema(example = "2023-06-01T00:00:00")]
+    pub archive_name: Option<String>,
+    /// The hexadecimal id of the created archive
+    #[serde(default)]
+    pub archive_id: Option<String>,
+    /// The point in time borg started to create the archive, in the local time of the drone
+    #[serde(default)]
+    pub archive_start: Option<NaiveDateTime>,
+    /// The point in time borg finished the archive, in the local time of the drone
+    #[serde(default)]
+    pub archive_end: Option<NaiveDateTime>,
+    /// The totals of the repository after the archive was created
+    #[serde(default)]
+    pub repository: Option<RepositoryTotals>,
+}
+
+/// A warning borg logged while creating an archive
+///
+/// Borg exits with code 1 if it logged warnings, e.g. if a file changed while it was
+/// backed up or could not be read.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct BorgWarning {
+    /// The message of the warning
+    #[schema(example = "/var/log/syslog: file changed while we backed it up")]
+    pub message: String,
+    /// The path the warning is about, if any
+    #[schema(example = "/var/log/syslog")]
+    pub path: Option<String>,
+}
+
+/// The kind of change of a file in an archive compared to the previous archives
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, ToSchema)]
+pub enum FileChange {
+    /// The file was added (status `A`)
+    Added,
+    /// The file was modified (status `M`)
+    Modified,
+}
+
+/// A file that changed compared to the previous archives
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct ChangedPath {
+    /// The kind of the change
+    pub change: FileChange,
+    /// The path of the file
+    #[schema(example = "/etc/nginx/nginx.conf")]
+    pub path: String,
+}
+
+/// The files that changed in an archive compared to the previous archives
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct ChangedFiles {
+    /// The number of added files
+    pub added: u64,
+    /// The number of modified files
+    pub modified: u64,
+    /// The number of unchanged files
+    pub unchanged: u64,
+    /// The changed paths, if the drone reports them
+    ///
+    /// The list is capped in size, see `paths_truncated`.
+    pub paths: Option<Vec<ChangedPath>>,
+    /// Whether `paths` doesn't contain all changed paths
+    #[serde(default)]
+    pub paths_truncated: bool,
+}
+
+/// The inventory of the host a drone runs on
+///
+/// It is sent with every report of the drone.
+/// Values that could not be determined by the drone are not set.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+pub struct HostInventory {
+    /// The hostname of the host
+    #[schema(example = "one-of-nine.example.com")]
+    pub hostname: Option<String>,
+    /// The name of the operating system
+    #[schema(example = "Debian GNU/Linux 12 (bookworm)")]
+    pub os: Option<String>,
+    /// The release of the kernel
+    #[schema(example = "6.1.0-10-amd64")]
+    pub kernel: Option<String>,
+    /// The version of borg
+    #[schema(example = "1.2.4")]
+    pub borg_version: Option<String>,
+    /// The version of borg-drone
+    #[schema(example = "0.1.0")]
+    pub drone_version: String,
+    /// The compression archives are created with
+    #[schema(example = "lz4")]
+    pub compression: String,
 }
 
 /// The report of the collected stats that sent from a drone to the vinculum
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, ToSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct StatReport {
     /// The stats of the pre hook
     pub pre_hook_stats: Option<HookStats>,
@@ -62,6 +192,26 @@ pub struct StatReport {
     pub create_stats: CreateStats,
     /// The stats of the post hook
     pub post_hook_stats: Option<HookStats>,
+    /// The warnings borg logged while creating the archive
+    #[serde(default)]
+    pub warnings: Vec<BorgWarning>,
+    /// The files that changed in the archive
+    #[serde(default)]
+    pub changed_files: Option<ChangedFiles>,
+    /// The point in time the drone started the run, before the pre hook
+    #[serde(default)]
+    pub start: Option<DateTime<Utc>>,
+    /// The point in time the drone finished the run, after the post hook
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>,
+    /// The point in time the drone sent the report.
+    ///
+    /// It is used by the vinculum to detect the clock skew of the drone.
+    #[serde(default)]
+    pub sent_at: Option<DateTime<Utc>>,
+    /// The inventory of the host the report was sent from
+    #[serde(default)]
+    pub host: Option<HostInventory>,
 }
 
 /// The report of an error
@@ -78,4 +228,106 @@ pub struct ErrorReport {
     /// Captured stderr
     #[schema(example = "This is the captured stderr")]
     pub stderr: Option<String>,
+    /// The inventory of the host the report was sent from
+    #[serde(default)]
+    pub host: Option<HostInventory>,
+}
+
+/// The report of a restore of an archive on a drone
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct RestoreReport {
+    /// The name of the restored archive
+    #[schema(example = "drone-2023-06-01T00:00:00")]
+    pub archive: String,
+    /// The paths in the archive that were restored, empty if the whole archive was restored
+    pub paths: Vec<String>,
+    /// The directory the archive was extracted into
+    #[schema(example = "/srv/restore")]
+    pub target: String,
+    /// Whether the restore succeeded
+    pub success: bool,
+    /// The error message, if the restore failed
+    pub error: Option<String>,
+    /// The duration of the restore in seconds
+    pub duration: u64,
+    /// The inventory of the host the report was sent from
+    #[serde(default)]
+    pub host: Option<HostInventory>,
+}
+
+/// The retention policy that is applied when pruning the archives of a repository
+///
+/// Refer to <https://borgbackup.readthedocs.io/en/stable/usage/prune.html> for the meaning
+/// of the rules.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, ToSchema)]
+pub struct PrunePolicy {
+    /// Keep all archives within this interval, e.g. `7d`
+    ///
+    /// The interval is a number followed by one of `H`, `d`, `w`, `m` or `y`.
+    #[schema(example = "2d")]
+    pub keep_within: Option<String>,
+    /// The number of hourly archives to keep
+    pub keep_hourly: Option<u16>,
+    /// The number of daily archives to keep
+    #[schema(example = 7)]
+    pub keep_daily: Option<u16>,
+    /// The number of weekly archives to keep
+    #[schema(example = 4)]
+    pub keep_weekly: Option<u16>,
+    /// The number of monthly archives to keep
+    #[schema(example = 6)]
+    pub keep_monthly: Option<u16>,
+    /// The number of yearly archives to keep
+    pub keep_yearly: Option<u16>,
+}
+
+impl PrunePolicy {
+    /// Check that the policy keeps any archives and that all rules are valid
+    pub fn validate(&self) -> Result<(), String> {
+        let counts = [
+            self.keep_hourly,
+            self.keep_daily,
+            self.keep_weekly,
+            self.keep_monthly,
+            self.keep_yearly,
+        ];
+
+        if counts.contains(&Some(0)) {
+            return Err("The number of archives to keep must be greater than 0".to_string());
+        }
+
+        if let Some(keep_within) = &self.keep_within {
+            let Some(quantifier) = keep_within.strip_suffix(['H', 'd', 'w', 'm', 'y']) else {
+                return Err(format!("Invalid unit in keep within {keep_within}"));
+            };
+            if !matches!(quantifier.parse::<u16>(), Ok(x) if x > 0) {
+                return Err(format!("Invalid interval in keep within {keep_within}"));
+            }
+        } else if counts.iter().all(Option::is_none) {
+            return Err("The policy does not contain any rule".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// The retention policy of a drone that is configured in the vinculum
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct GetPrunePolicyResponse {
+    /// The retention policy, not set if there is none configured
+    pub policy: Option<PrunePolicy>,
+}
+
+/// The stats of the pruning of a repository
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct PruneStats {
+    /// The number of archives that were pruned
+    pub pruned: u64,
+    /// The number of archives that were kept
+    pub kept: u64,
+    /// The duration of the prune operation in seconds
+    pub duration: u64,
+    /// The inventory of the host the report was sent from
+    #[serde(default)]
+    pub host: Option<HostInventory>,
 }